@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use kier::{Game, Player, PlayerID, Request, Response, Rules};
+
+/// Messages to deliver, each addressed to the connection of a session.
+pub type Outbox = Vec<(mio::Token, Response)>;
+
+/// A `kier::Game` shared by the sessions playing in it, with the
+/// connection each player is reached on.
+pub struct GameHost {
+    game: Game,
+    seats: HashMap<PlayerID, mio::Token>,
+    /// How many events of the encounter have been pushed so far.
+    sent: usize,
+}
+
+impl GameHost {
+    pub fn new(
+        rules: &Rules,
+        players: Vec<Player>,
+        seats: HashMap<PlayerID, mio::Token>,
+        seed: u64,
+    ) -> Self {
+        let game = Game::new(players, Vec::new(), rules, seed);
+        let sent = game.encounter.events().len();
+        GameHost { game, seats, sent }
+    }
+
+    /// The opening view of every player.
+    pub fn start(&self) -> Outbox {
+        let mut out = Vec::new();
+        for (id, token) in self.sorted_seats() {
+            out.extend(self.state(token, id));
+        }
+        out
+    }
+
+    /// Applies a game action sent by `player`.  A refused action is
+    /// answered to the sender alone; an accepted one is broadcast.
+    pub fn handle(&mut self, player: PlayerID, request: &Request) -> Outbox {
+        match self.game.handle(player, request) {
+            Ok(()) => self.broadcast(),
+            Err(err) => match self.seats.get(&player) {
+                Some(&token) => vec![(token, Response::Error(err))],
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// Concedes for the character of a player whose session is gone.
+    /// The others play on while two or more sides remain.
+    pub fn leave(&mut self, player: PlayerID) -> Outbox {
+        self.seats.remove(&player);
+        if self.is_done() {
+            return Vec::new();
+        }
+        self.handle(player, &Request::Concede)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.game.encounter.done
+    }
+
+    fn sorted_seats(&self) -> Vec<(PlayerID, mio::Token)> {
+        let mut seats: Vec<_> = self.seats.iter()
+            .map(|(&id, &token)| (id, token))
+            .collect();
+        seats.sort();
+        seats
+    }
+
+    /// The current view of `id`, followed by the outcome if the game is
+    /// over.
+    fn state(&self, token: mio::Token, id: PlayerID) -> Outbox {
+        let mut out = vec![(token, Response::State(self.game.view(id)))];
+        if self.is_done() {
+            out.push((token, Response::GameOver {
+                outcome: self.game.outcome().cloned(),
+            }));
+        }
+        out
+    }
+
+    /// Everything each seated player has not seen since the last
+    /// broadcast: new events they may see, then their view.
+    fn broadcast(&mut self) -> Outbox {
+        let mut out = Vec::new();
+        for (id, token) in self.sorted_seats() {
+            for event in self.game.view_events(id, self.sent) {
+                out.push((token, Response::Event(event)));
+            }
+            out.extend(self.state(token, id));
+        }
+        self.sent = self.game.encounter.events().len();
+        out
+    }
+}
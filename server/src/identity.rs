@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fs;
+
+use kier::PlayerID;
+use ring::digest;
+
+/// SHA-256 digest of a certificate's DER encoding.
+pub type Fingerprint = [u8; 32];
+
+/// How client certificates map to players.
+pub enum Identities {
+    /// Any certificate the verifier accepts, as the player numbered by
+    /// the first eight bytes of its fingerprint.
+    Fingerprint,
+
+    /// Only the certificates listed, each as the player given for it.
+    Allowlist(HashMap<Fingerprint, PlayerID>),
+}
+
+pub fn fingerprint(cert: &rustls::Certificate) -> Fingerprint {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(digest::digest(&digest::SHA256, &cert.0).as_ref());
+    out
+}
+
+fn parse_fingerprint(text: &str) -> Option<Fingerprint> {
+    let hex: Vec<u8> = text.bytes().filter(|&b| b != b':').collect();
+    if hex.len() != 64 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+
+    let mut out = [0u8; 32];
+    for (byte, pair) in out.iter_mut().zip(hex.chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(out)
+}
+
+impl Identities {
+    /// Read an allowlist with one certificate per line: its SHA-256
+    /// fingerprint in hex, optionally colon-separated as printed by
+    /// `openssl x509 -fingerprint -sha256`, then the player id.  Blank
+    /// lines and `#` comments are ignored.
+    pub fn load(filename: &str) -> Self {
+        let text = fs::read_to_string(filename).expect("cannot open player allowlist");
+        let mut players = HashMap::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let entry = words
+                .next()
+                .and_then(parse_fingerprint)
+                .zip(words.next().and_then(|id| id.parse().ok()));
+            match entry {
+                Some((fingerprint, id)) if words.next().is_none() => {
+                    players.insert(fingerprint, id);
+                }
+                _ => panic!(
+                    "{}:{}: expected a SHA-256 fingerprint and a player id",
+                    filename,
+                    n + 1
+                ),
+            }
+        }
+
+        Identities::Allowlist(players)
+    }
+
+    /// The player `cert` identifies, if any.
+    pub fn player(&self, cert: &rustls::Certificate) -> Option<PlayerID> {
+        let fingerprint = fingerprint(cert);
+        match self {
+            Identities::Fingerprint => {
+                let mut id = [0u8; 8];
+                id.copy_from_slice(&fingerprint[..8]);
+                Some(PlayerID::from_be_bytes(id))
+            }
+            Identities::Allowlist(players) => players.get(&fingerprint).copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn counting() -> Fingerprint {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        out
+    }
+
+    fn colons(hex: &str) -> String {
+        hex.as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    fn write_list(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "kier-server-{}-{}.txt",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_fingerprints() {
+        assert_eq!(parse_fingerprint(HEX), Some(counting()));
+        assert_eq!(parse_fingerprint(&HEX.to_uppercase()), Some(counting()));
+        assert_eq!(parse_fingerprint(&colons(HEX)), Some(counting()));
+
+        assert_eq!(parse_fingerprint(&HEX[2..]), None);
+        assert_eq!(parse_fingerprint(&format!("{}00", HEX)), None);
+        assert_eq!(parse_fingerprint(&format!("zz{}", &HEX[2..])), None);
+        assert_eq!(parse_fingerprint(&format!("+1{}", &HEX[2..])), None);
+        assert_eq!(parse_fingerprint(""), None);
+    }
+
+    #[test]
+    fn loads_allowlist() {
+        let cert = rustls::Certificate(b"some certificate".to_vec());
+        let text = format!(
+            "# players\n\n{} 7\n  {}   42  # with a comment\n",
+            HEX,
+            colons(&hex(&fingerprint(&cert))),
+        );
+        let identities = Identities::load(&write_list("allow", &text));
+        match &identities {
+            Identities::Allowlist(players) => {
+                assert_eq!(players.len(), 2);
+                assert_eq!(players.get(&counting()), Some(&7));
+            }
+            Identities::Fingerprint => panic!("expected an allowlist"),
+        }
+        assert_eq!(identities.player(&cert), Some(42));
+        assert_eq!(identities.player(&rustls::Certificate(b"other".to_vec())), None);
+    }
+
+    #[test]
+    #[should_panic(expected = ":2: expected a SHA-256 fingerprint and a player id")]
+    fn rejects_bad_lines() {
+        Identities::load(&write_list("bad", &format!("{} 1\n{} 2 3\n", HEX, HEX)));
+    }
+
+    #[test]
+    fn fingerprint_players() {
+        let cert = rustls::Certificate(b"some certificate".to_vec());
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&fingerprint(&cert)[..8]);
+        assert_eq!(
+            Identities::Fingerprint.player(&cert),
+            Some(PlayerID::from_be_bytes(id))
+        );
+    }
+}
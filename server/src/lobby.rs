@@ -0,0 +1,544 @@
+use std::collections::{BTreeMap, HashMap};
+
+use kier::loader::parse_deck;
+use kier::protocol::{ProtocolError, RoomID, RoomInfo};
+use kier::{
+    Character, Deck, DeckRules, Player, PlayerID, Request, Response, Rules,
+};
+
+use crate::game::{GameHost, Outbox};
+
+/// Most seats a room may have.
+const MAX_SEATS: usize = 8;
+
+/// A player waiting in a room or in the queue, with the deck they
+/// brought.
+struct Member {
+    player: PlayerID,
+    token: mio::Token,
+    deck: Deck,
+    ready: bool,
+}
+
+/// Seats waiting to be filled, and the game once they all are.
+struct Room {
+    seats: usize,
+    members: Vec<Member>,
+    game: Option<GameHost>,
+}
+
+impl Room {
+    fn info(&self, id: RoomID) -> RoomInfo {
+        RoomInfo {
+            id,
+            seats: self.seats,
+            members: self.members
+                .iter()
+                .map(|m| (m.player, m.ready))
+                .collect(),
+            started: self.game.is_some(),
+        }
+    }
+
+    /// The room as it is now, sent to each member.
+    fn notify(&self, id: RoomID) -> Outbox {
+        let info = self.info(id);
+        self.members
+            .iter()
+            .map(|m| (m.token, Response::Room(info.clone())))
+            .collect()
+    }
+}
+
+/// Every room hosted by the server, the sessions logged in to it and
+/// the queue for two-player matches.  Rooms are numbered in creation
+/// order and each plays its game with its own seed, derived from the
+/// lobby's.
+pub struct Lobby {
+    rules: Rules,
+    deck_rules: DeckRules,
+    seed: u64,
+    rooms: BTreeMap<RoomID, Room>,
+    next_room: RoomID,
+    queue: Vec<Member>,
+    online: HashMap<PlayerID, mio::Token>,
+}
+
+impl Lobby {
+    pub fn new(rules: Rules, deck_rules: DeckRules, seed: u64) -> Self {
+        Lobby {
+            rules,
+            deck_rules,
+            seed,
+            rooms: BTreeMap::new(),
+            next_room: 1,
+            queue: Vec::new(),
+            online: HashMap::new(),
+        }
+    }
+
+    /// Handles a request from the session on `token`, which acts as
+    /// `player` once it has joined.
+    pub fn handle(
+        &mut self,
+        token: mio::Token,
+        player: &mut Option<PlayerID>,
+        request: Request,
+    ) -> Outbox {
+        let result = match (request, *player) {
+            (Request::Join { player: id }, None) => {
+                self.login(token, id).map(|()| {
+                    *player = Some(id);
+                    vec![(token, Response::Rooms(self.list()))]
+                })
+            }
+            (Request::Join { .. }, Some(_)) => Err(ProtocolError::AlreadyJoined),
+            (_, None) => Err(ProtocolError::NotJoined),
+            (Request::ListRooms, Some(_)) => {
+                Ok(vec![(token, Response::Rooms(self.list()))])
+            }
+            (Request::CreateRoom { seats }, Some(_)) => {
+                self.create(seats).map(|id| {
+                    vec![(token, Response::Room(self.rooms[&id].info(id)))]
+                })
+            }
+            (Request::JoinRoom { room, deck }, Some(id)) => {
+                self.join_room(token, id, room, &deck)
+            }
+            (Request::Ready, Some(id)) => self.ready(token, id),
+            (Request::LeaveRoom, Some(id)) => {
+                self.leave(id).map(|mut out| {
+                    out.push((token, Response::Rooms(self.list())));
+                    out
+                })
+            }
+            (Request::Queue { deck }, Some(id)) => self.enqueue(token, id, &deck),
+            (request, Some(id)) => self.play(id, &request),
+        };
+
+        result.unwrap_or_else(|err| vec![(token, Response::Error(err))])
+    }
+
+    /// Logs out the session on a closed connection.  Its character in a
+    /// game it was playing concedes.
+    pub fn disconnect(&mut self, token: mio::Token, player: Option<PlayerID>) -> Outbox {
+        let id = match player {
+            Some(id) if self.online.get(&id) == Some(&token) => id,
+            _ => return Vec::new(),
+        };
+        self.online.remove(&id);
+        self.leave(id).unwrap_or_default()
+    }
+
+    /// Logs in the session on `token` as player `id`, unless another
+    /// session already plays as them.
+    pub fn login(&mut self, token: mio::Token, id: PlayerID) -> Result<(), ProtocolError> {
+        if self.online.contains_key(&id) {
+            return Err(ProtocolError::SeatTaken(id));
+        }
+        self.online.insert(id, token);
+        Ok(())
+    }
+
+    fn list(&self) -> Vec<RoomInfo> {
+        self.rooms
+            .iter()
+            .map(|(&id, room)| room.info(id))
+            .collect()
+    }
+
+    fn create(&mut self, seats: usize) -> Result<RoomID, ProtocolError> {
+        if !(2..=MAX_SEATS).contains(&seats) {
+            return Err(ProtocolError::InvalidSeats(seats));
+        }
+        let id = self.next_room;
+        self.next_room += 1;
+        self.rooms.insert(id, Room {
+            seats,
+            members: Vec::new(),
+            game: None,
+        });
+        Ok(id)
+    }
+
+    fn room_of(&self, player: PlayerID) -> Option<RoomID> {
+        self.rooms
+            .iter()
+            .find(|(_, room)| room.members.iter().any(|m| m.player == player))
+            .map(|(&id, _)| id)
+    }
+
+    fn is_free(&self, player: PlayerID) -> Result<(), ProtocolError> {
+        if self.room_of(player).is_some()
+            || self.queue.iter().any(|m| m.player == player)
+        {
+            return Err(ProtocolError::InRoom);
+        }
+        Ok(())
+    }
+
+    /// A deck built from a list in the deck file format, if it keeps to
+    /// the server's deck rules.
+    fn build_deck(&self, src: &str) -> Result<Deck, ProtocolError> {
+        let cards = &self.rules.card_list;
+        let list = parse_deck(src, "deck", cards)
+            .map_err(|err| ProtocolError::BadDeck(err.to_string()))?;
+        self.deck_rules
+            .build(&list, None, cards)
+            .map_err(|violations| {
+                let messages: Vec<String> = violations
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect();
+                ProtocolError::BadDeck(messages.join("; "))
+            })
+    }
+
+    fn join_room(
+        &mut self,
+        token: mio::Token,
+        player: PlayerID,
+        id: RoomID,
+        deck: &str,
+    ) -> Result<Outbox, ProtocolError> {
+        self.is_free(player)?;
+        let room = self.rooms.get(&id).ok_or(ProtocolError::NoSuchRoom(id))?;
+        if room.game.is_some() || room.members.len() >= room.seats {
+            return Err(ProtocolError::RoomFull(id));
+        }
+        let deck = self.build_deck(deck)?;
+
+        let room = self.rooms.get_mut(&id).unwrap();
+        room.members.push(Member {
+            player,
+            token,
+            deck,
+            ready: false,
+        });
+        Ok(room.notify(id))
+    }
+
+    fn ready(&mut self, token: mio::Token, player: PlayerID) -> Result<Outbox, ProtocolError> {
+        let id = self.room_of(player).ok_or(ProtocolError::NotInRoom)?;
+        let room = self.rooms.get_mut(&id).unwrap();
+        if room.game.is_some() {
+            return Ok(vec![(token, Response::Room(room.info(id)))]);
+        }
+        for member in room.members.iter_mut() {
+            if member.player == player {
+                member.ready = true;
+            }
+        }
+        let out = self.try_start(id);
+        if out.is_empty() {
+            return Ok(self.rooms[&id].notify(id));
+        }
+        Ok(out)
+    }
+
+    /// Starts the game in room `id` once every seat is filled and ready.
+    fn try_start(&mut self, id: RoomID) -> Outbox {
+        let room = self.rooms.get_mut(&id).unwrap();
+        if room.game.is_some()
+            || room.members.len() < room.seats
+            || !room.members.iter().all(|m| m.ready)
+        {
+            return Vec::new();
+        }
+
+        let mut players = Vec::new();
+        let mut seats = HashMap::new();
+        for (side, member) in room.members.iter().enumerate() {
+            let mut character = Character::new(Default::default(), member.deck.clone());
+            character.side = side;
+            players.push(Player {
+                id: member.player,
+                character,
+            });
+            seats.insert(member.player, member.token);
+        }
+        debug!("starting game in room {}", id);
+        let seed = self.seed.wrapping_add(id);
+        room.game = Some(GameHost::new(&self.rules, players, seats, seed));
+
+        let mut out = room.notify(id);
+        out.extend(room.game.as_ref().unwrap().start());
+        out
+    }
+
+    /// Takes `player` out of the queue or their room, conceding for their
+    /// character if the game has started, so that they are free to play
+    /// elsewhere.  Rooms left empty or with a finished game are closed.
+    fn leave(&mut self, player: PlayerID) -> Result<Outbox, ProtocolError> {
+        if let Some(i) = self.queue.iter().position(|m| m.player == player) {
+            self.queue.remove(i);
+            return Ok(Vec::new());
+        }
+
+        let id = self.room_of(player).ok_or(ProtocolError::NotInRoom)?;
+        let room = self.rooms.get_mut(&id).unwrap();
+        room.members.retain(|m| m.player != player);
+        let out = match room.game.as_mut() {
+            Some(game) => game.leave(player),
+            None => room.notify(id),
+        };
+        self.close_if_over(id);
+        Ok(out)
+    }
+
+    fn enqueue(
+        &mut self,
+        token: mio::Token,
+        player: PlayerID,
+        deck: &str,
+    ) -> Result<Outbox, ProtocolError> {
+        self.is_free(player)?;
+        let deck = self.build_deck(deck)?;
+        self.queue.push(Member {
+            player,
+            token,
+            deck,
+            ready: true,
+        });
+        if self.queue.len() < 2 {
+            return Ok(vec![(token, Response::Queued)]);
+        }
+
+        let id = self.create(2)?;
+        let members: Vec<Member> = self.queue.drain(..2).collect();
+        self.rooms.get_mut(&id).unwrap().members = members;
+        Ok(self.try_start(id))
+    }
+
+    fn play(&mut self, player: PlayerID, request: &Request) -> Result<Outbox, ProtocolError> {
+        let id = self.room_of(player).ok_or(ProtocolError::NotInGame)?;
+        let game = self.rooms
+            .get_mut(&id)
+            .unwrap()
+            .game
+            .as_mut()
+            .ok_or(ProtocolError::NotInGame)?;
+        let out = game.handle(player, request);
+        self.close_if_over(id);
+        Ok(out)
+    }
+
+    fn close_if_over(&mut self, id: RoomID) {
+        let over = match self.rooms.get(&id) {
+            Some(room) => {
+                room.members.is_empty()
+                    || room.game.as_ref().is_some_and(|game| game.is_done())
+            }
+            None => false,
+        };
+        if over {
+            debug!("closing room {}", id);
+            self.rooms.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use kier::effect::{self, Who};
+    use kier::{Card, Effect, EndCondition, Targets, Trait};
+
+    use super::*;
+
+    fn lobby() -> Lobby {
+        let mut strike = Card::new("Strike", "", effect::no_effect);
+        strike.targets = Targets::Enemy;
+        strike.effects.push(Effect::ModifyTrait { who: Who::Target, id: 0, by: -5 });
+        let mut rules = Rules::new(Arc::new(vec![strike]), Arc::new(Vec::new()));
+        let mut health = Trait::new("health");
+        health.default = 20;
+        rules.trait_list = Arc::new(vec![health]);
+        rules.end_conditions.push(EndCondition::Defeat { id: 0 });
+        Lobby::new(rules, DeckRules::new(5, 20, 10), 1)
+    }
+
+    /// Logs in player `id` on token `id` and returns its session state.
+    fn join(lobby: &mut Lobby, id: PlayerID) -> Option<PlayerID> {
+        let mut player = None;
+        let out = lobby.handle(mio::Token(id as usize), &mut player, Request::Join { player: id });
+        assert!(matches!(out[..], [(_, Response::Rooms(_))]));
+        player
+    }
+
+    fn send(lobby: &mut Lobby, player: PlayerID, request: Request) -> Outbox {
+        lobby.handle(mio::Token(player as usize), &mut Some(player), request)
+    }
+
+    fn errors(out: &Outbox) -> Vec<ProtocolError> {
+        out.iter()
+            .filter_map(|(_, response)| match response {
+                Response::Error(err) => Some(err.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn states(out: &Outbox) -> usize {
+        out.iter()
+            .filter(|(_, response)| matches!(response, Response::State(_)))
+            .count()
+    }
+
+    fn deck() -> String {
+        "6 Strike".to_string()
+    }
+
+    /// A started room with `seats` players numbered from 1.
+    fn started(lobby: &mut Lobby, seats: usize) -> RoomID {
+        let out = send(lobby, 1, Request::CreateRoom { seats });
+        let room = match &out[..] {
+            [(_, Response::Room(info))] => info.id,
+            other => panic!("expected a room, found {:?}", other),
+        };
+        for id in 1..=seats as PlayerID {
+            join(lobby, id);
+            assert!(errors(&send(lobby, id, Request::JoinRoom { room, deck: deck() })).is_empty());
+        }
+        for id in 1..=seats as PlayerID {
+            let out = send(lobby, id, Request::Ready);
+            assert_eq!(states(&out) > 0, id == seats as PlayerID);
+        }
+        room
+    }
+
+    #[test]
+    fn seats_are_taken_once() {
+        let mut lobby = lobby();
+        join(&mut lobby, 1);
+        let out = lobby.handle(mio::Token(9), &mut None, Request::Join { player: 1 });
+        assert_eq!(errors(&out), vec![ProtocolError::SeatTaken(1)]);
+
+        let out = lobby.handle(mio::Token(9), &mut None, Request::ListRooms);
+        assert_eq!(errors(&out), vec![ProtocolError::NotJoined]);
+
+        assert!(lobby.disconnect(mio::Token(1), Some(1)).is_empty());
+        let out = lobby.handle(mio::Token(9), &mut None, Request::Join { player: 1 });
+        assert!(errors(&out).is_empty());
+    }
+
+    #[test]
+    fn rooms_refuse_bad_joins() {
+        let mut lobby = lobby();
+        join(&mut lobby, 1);
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::CreateRoom { seats: 1 })),
+            vec![ProtocolError::InvalidSeats(1)]
+        );
+        send(&mut lobby, 1, Request::CreateRoom { seats: 2 });
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::JoinRoom { room: 5, deck: deck() })),
+            vec![ProtocolError::NoSuchRoom(5)]
+        );
+        let short = Request::JoinRoom {
+            room: 1,
+            deck: "2 Strike".to_string(),
+        };
+        assert!(matches!(
+            errors(&send(&mut lobby, 1, short))[..],
+            [ProtocolError::BadDeck(_)]
+        ));
+        send(&mut lobby, 1, Request::JoinRoom { room: 1, deck: deck() });
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::JoinRoom { room: 1, deck: deck() })),
+            vec![ProtocolError::InRoom]
+        );
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::EndTurn)),
+            vec![ProtocolError::NotInGame]
+        );
+
+        for id in 2..=3 {
+            join(&mut lobby, id);
+        }
+        send(&mut lobby, 2, Request::JoinRoom { room: 1, deck: deck() });
+        assert_eq!(
+            errors(&send(&mut lobby, 3, Request::JoinRoom { room: 1, deck: deck() })),
+            vec![ProtocolError::RoomFull(1)]
+        );
+    }
+
+    #[test]
+    fn starts_once_all_ready() {
+        let mut lobby = lobby();
+        let room = started(&mut lobby, 3);
+        assert!(lobby.rooms[&room].game.is_some());
+        assert!(errors(&send(&mut lobby, 1, Request::EndTurn)).is_empty());
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::EndTurn)),
+            vec![ProtocolError::Action(kier::ActionError::NotYourTurn {
+                character: 0,
+                active: 1,
+            })]
+        );
+    }
+
+    #[test]
+    fn leaving_a_game_frees_the_player() {
+        let mut lobby = lobby();
+        let room = started(&mut lobby, 3);
+        let out = send(&mut lobby, 2, Request::LeaveRoom);
+        assert!(matches!(out.last(), Some((_, Response::Rooms(_)))));
+        assert!(!out.iter().any(|(_, response)| matches!(response, Response::GameOver { .. })));
+        assert!(!out.iter().any(|(to, response)| {
+            *to == mio::Token(2) && matches!(response, Response::State(_))
+        }));
+        assert_eq!(lobby.room_of(2), None);
+        assert!(errors(&send(&mut lobby, 2, Request::Queue { deck: deck() })).is_empty());
+
+        // Two sides remain, so the others play on, skipping the turn of
+        // the player who left.
+        assert_eq!(lobby.room_of(1), Some(room));
+        let out = send(&mut lobby, 1, Request::EndTurn);
+        assert!(errors(&out).is_empty());
+        assert_eq!(states(&out), 2);
+        assert!(errors(&send(&mut lobby, 3, Request::EndTurn)).is_empty());
+
+        // The last other side leaving ends the game and closes the room.
+        let out = lobby.disconnect(mio::Token(3), Some(3));
+        assert!(out.iter().any(|(to, response)| {
+            *to == mio::Token(1) && matches!(response, Response::GameOver { .. })
+        }));
+        assert!(lobby.rooms.is_empty());
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::EndTurn)),
+            vec![ProtocolError::NotInGame]
+        );
+    }
+
+    #[test]
+    fn queue_pairs_players() {
+        let mut lobby = lobby();
+        for id in 1..=3 {
+            join(&mut lobby, id);
+        }
+        let out = send(&mut lobby, 1, Request::Queue { deck: deck() });
+        assert!(matches!(out[..], [(_, Response::Queued)]));
+        assert_eq!(
+            errors(&send(&mut lobby, 1, Request::Queue { deck: deck() })),
+            vec![ProtocolError::InRoom]
+        );
+
+        let out = send(&mut lobby, 2, Request::Queue { deck: deck() });
+        assert_eq!(states(&out), 2);
+        assert_eq!(lobby.room_of(1), lobby.room_of(2));
+        assert!(lobby.queue.is_empty());
+
+        send(&mut lobby, 3, Request::Queue { deck: deck() });
+        assert!(lobby.disconnect(mio::Token(3), Some(3)).is_empty());
+        assert!(lobby.queue.is_empty());
+
+        // Disconnecting concedes the game, which closes the room.
+        let out = lobby.disconnect(mio::Token(1), Some(1));
+        assert!(out.iter().any(|(to, response)| {
+            *to == mio::Token(2) && matches!(response, Response::GameOver { .. })
+        }));
+        assert!(lobby.rooms.is_empty());
+    }
+}
@@ -12,6 +12,7 @@ use std::fs;
 use std::io;
 use std::io::{BufReader, Read, Write};
 use std::net;
+use std::path::Path;
 
 #[macro_use]
 extern crate serde_derive;
@@ -21,6 +22,18 @@ use rustls::server::{
 };
 use rustls::{self, RootCertStore};
 
+use kier::loader::load_rules;
+use kier::protocol::{self, ProtocolError, PROTOCOL_VERSION};
+use kier::{DeckRules, PlayerID, Request, Response};
+
+mod game;
+mod identity;
+mod lobby;
+
+use game::Outbox;
+use identity::Identities;
+use lobby::Lobby;
+
 // Token for our listening socket.
 const LISTENER: mio::Token = mio::Token(0);
 
@@ -36,6 +49,10 @@ enum ServerMode {
 
     /// Forward traffic to/from given port on localhost.
     Forward(u16),
+
+    /// Speak the kier protocol, with each connection a player session
+    /// in the lobby hosted by the server.
+    Game,
 }
 
 /// This binds together a TCP listening socket, some outstanding
@@ -46,16 +63,26 @@ struct TlsServer {
     next_id: usize,
     tls_config: Arc<rustls::ServerConfig>,
     mode: ServerMode,
+    lobby: Option<Lobby>,
+    identities: Option<Arc<Identities>>,
 }
 
 impl TlsServer {
-    fn new(server: TcpListener, mode: ServerMode, cfg: Arc<rustls::ServerConfig>) -> Self {
+    fn new(
+        server: TcpListener,
+        mode: ServerMode,
+        cfg: Arc<rustls::ServerConfig>,
+        lobby: Option<Lobby>,
+        identities: Option<Arc<Identities>>,
+    ) -> Self {
         TlsServer {
             server,
             connections: HashMap::new(),
             next_id: 2,
             tls_config: cfg,
             mode,
+            lobby,
+            identities,
         }
     }
 
@@ -72,7 +99,10 @@ impl TlsServer {
                     let token = mio::Token(self.next_id);
                     self.next_id += 1;
 
-                    let mut connection = OpenConnection::new(socket, token, mode, tls_conn);
+                    let identities = self.identities.clone();
+
+                    let mut connection =
+                        OpenConnection::new(socket, token, mode, tls_conn, identities);
                     connection.register(registry);
                     self.connections
                         .insert(token, connection);
@@ -98,8 +128,62 @@ impl TlsServer {
                 .unwrap()
                 .ready(registry, event);
 
+            self.route_requests(registry, token);
+
             if self.connections[&token].is_closed() {
-                self.connections.remove(&token);
+                let conn = self.connections.remove(&token).unwrap();
+                if let Some(lobby) = self.lobby.as_mut() {
+                    let outbox = lobby.disconnect(token, conn.player);
+                    self.deliver(registry, outbox);
+                }
+            }
+        }
+    }
+
+    /// Hand the requests decoded on `token` to the lobby, and deliver
+    /// whatever it answers to the connections concerned.
+    fn route_requests(&mut self, registry: &mio::Registry, token: mio::Token) {
+        let lobby = match self.lobby.as_mut() {
+            Some(lobby) => lobby,
+            None => return,
+        };
+        let conn = self.connections.get_mut(&token).unwrap();
+        let requests = std::mem::take(&mut conn.requests);
+
+        let mut outbox = Vec::new();
+        for request in requests {
+            match request {
+                // The join implied by the session's certificate: the
+                // session is only welcomed once the lobby lets it in.
+                Request::Join { player }
+                    if conn.identity == Some(player) && conn.player.is_none() =>
+                {
+                    if let Err(err) = lobby.login(token, player) {
+                        conn.refuse(err);
+                        break;
+                    }
+                    conn.player = Some(player);
+                    conn.send(&Response::Welcome {
+                        version: PROTOCOL_VERSION,
+                    });
+                    outbox.extend(lobby.handle(token, &mut conn.player, Request::ListRooms));
+                }
+                request => outbox.extend(lobby.handle(token, &mut conn.player, request)),
+            }
+        }
+        if !conn.is_closed() {
+            conn.reregister(registry);
+        }
+        self.deliver(registry, outbox);
+    }
+
+    fn deliver(&mut self, registry: &mio::Registry, outbox: Outbox) {
+        for (to, response) in outbox {
+            if let Some(conn) = self.connections.get_mut(&to) {
+                conn.send(&response);
+                if !conn.is_closed() {
+                    conn.reregister(registry);
+                }
             }
         }
     }
@@ -119,6 +203,16 @@ struct OpenConnection {
     tls_conn: rustls::ServerConnection,
     back: Option<TcpStream>,
     sent_http_response: bool,
+    /// Game mode: plaintext not yet decoded into a whole frame.
+    incoming: Vec<u8>,
+    /// Game mode: requests waiting to be routed to the game.
+    requests: Vec<Request>,
+    greeted: bool,
+    player: Option<PlayerID>,
+    /// Game mode with client authentication: how certificates map to
+    /// players, and the player this session's certificate identified.
+    identities: Option<Arc<Identities>>,
+    identity: Option<PlayerID>,
 }
 
 /// Open a plaintext TCP-level connection for forwarded connections.
@@ -154,6 +248,7 @@ impl OpenConnection {
         token: mio::Token,
         mode: ServerMode,
         tls_conn: rustls::ServerConnection,
+        identities: Option<Arc<Identities>>,
     ) -> OpenConnection {
         let back = open_back(&mode);
         OpenConnection {
@@ -165,6 +260,12 @@ impl OpenConnection {
             tls_conn,
             back,
             sent_http_response: false,
+            incoming: Vec::new(),
+            requests: Vec::new(),
+            greeted: false,
+            player: None,
+            identities,
+            identity: None,
         }
     }
 
@@ -308,6 +409,100 @@ impl OpenConnection {
                     .write_all(buf)
                     .unwrap();
             }
+            ServerMode::Game => {
+                self.incoming.extend_from_slice(buf);
+                self.decode_requests();
+            }
+        }
+    }
+
+    /// Decode every whole frame received so far.  The first request must
+    /// be a hello with our protocol version; later ones are queued for
+    /// the game.  With client authentication the hello is answered once
+    /// the lobby has logged in the certificate's player, which is queued
+    /// as a join ahead of everything else.
+    fn decode_requests(&mut self) {
+        loop {
+            let (request, used) = match protocol::decode::<Request>(&self.incoming) {
+                Ok(Some(decoded)) => decoded,
+                Ok(None) => return,
+                Err(err) => {
+                    self.refuse(ProtocolError::Malformed(err.to_string()));
+                    return;
+                }
+            };
+            self.incoming.drain(..used);
+
+            match (self.greeted, request) {
+                (false, Request::Hello { version }) if version == PROTOCOL_VERSION => {
+                    if let Err(err) = self.authenticate() {
+                        self.refuse(err);
+                        return;
+                    }
+                    self.greeted = true;
+                    match self.identity {
+                        Some(player) => self.requests.push(Request::Join { player }),
+                        None => self.send(&Response::Welcome {
+                            version: PROTOCOL_VERSION,
+                        }),
+                    }
+                }
+                (false, Request::Hello { version }) => {
+                    self.refuse(ProtocolError::VersionMismatch {
+                        server: PROTOCOL_VERSION,
+                        client: version,
+                    });
+                    return;
+                }
+                (false, _) => {
+                    self.refuse(ProtocolError::NoHandshake);
+                    return;
+                }
+                (true, Request::Join { .. }) if self.identities.is_some() => {
+                    self.send(&Response::Error(match self.identity {
+                        Some(_) => ProtocolError::AlreadyJoined,
+                        None => ProtocolError::Unauthenticated,
+                    }));
+                }
+                (true, request) => self.requests.push(request),
+            }
+        }
+    }
+
+    /// With client authentication on, find the player named by the
+    /// peer's certificate.  The handshake is complete by the time any
+    /// request arrives, so the certificate is known.
+    fn authenticate(&mut self) -> Result<(), ProtocolError> {
+        let identities = match &self.identities {
+            Some(identities) => identities,
+            None => return Ok(()),
+        };
+        let player = self.tls_conn
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .and_then(|cert| identities.player(cert))
+            .ok_or(ProtocolError::Unauthenticated)?;
+
+        debug!("session authenticated as player {}", player);
+        self.identity = Some(player);
+        Ok(())
+    }
+
+    /// Send a final error and close the session once it is written.
+    fn refuse(&mut self, err: ProtocolError) {
+        debug!("refusing session: {}", err);
+        self.send(&Response::Error(err));
+        self.incoming.clear();
+        self.tls_conn.send_close_notify();
+    }
+
+    fn send(&mut self, response: &Response) {
+        match protocol::encode(response) {
+            Ok(frame) => self.tls_conn
+                .writer()
+                .write_all(&frame)
+                .unwrap(),
+            Err(err) => error!("cannot encode response: {}", err),
         }
     }
 
@@ -400,6 +595,14 @@ Runs a TLS server on :PORT.  The default PORT is 443.
 connection.
 `forward' means the server forwards plaintext to a connection made to
 localhost:fport.
+`game' means the server hosts rooms where clients speaking the kier
+protocol play encounters with the cards in CARDFILE, each with a deck
+of their own.  `--trait' declares a trait as NAME=DEFAULT, and
+`--refill' resets a trait at the start of each turn as NAME=VALUE.
+With `--auth', game sessions are identified by their client
+certificate: a certificate listed in the `--players' file plays as the
+player given there, and without that file any accepted certificate
+plays as the player numbered by its SHA-256 fingerprint.
 `--certs' names the full certificate chain, `--key' provides the
 RSA private key.
 Usage:
@@ -409,6 +612,9 @@ Usage:
      [--proto PROTO ...] [--protover PROTOVER ...] [options] http
   tlsserver-mio --certs CERTFILE --key KEYFILE [--suite SUITE ...] \
      [--proto PROTO ...] [--protover PROTOVER ...] [options] forward <fport>
+  tlsserver-mio --certs CERTFILE --key KEYFILE [--suite SUITE ...] \
+     [--proto PROTO ...] [--protover PROTOVER ...] [options] game \
+     --cards CARDFILE [--trait SPEC]... [--refill SPEC]...
   tlsserver-mio (--version | -v)
   tlsserver-mio (--help | -h)
 Options:
@@ -425,6 +631,9 @@ Options:
                         signed by those roots provided in CERTFILE.
     --require-auth      Send a fatal alert if the client does not complete client
                         authentication.
+    --players FILE      Only let in game sessions whose certificate is listed
+                        in FILE, as lines of a SHA-256 fingerprint and the
+                        player id it stands for.  Needs --auth.
     --resumption        Support session resumption.
     --tickets           Support tickets.
     --protover VERSION  Disable default TLS version list, and use
@@ -433,6 +642,15 @@ Options:
                         SUITE instead.  May be used multiple times.
     --proto PROTOCOL    Negotiate PROTOCOL using ALPN.
                         May be used multiple times.
+    --cards CARDFILE    Read card definitions from CARDFILE.
+    --trait SPEC        Declare a trait as NAME=DEFAULT.
+    --refill SPEC       Refill a trait each turn as NAME=VALUE.
+    --defeat NAME       Trait that decides defeat, by default the first.
+    --seed N            Seed for the hosted games [default: 0].
+    --min-deck N        Fewest cards a deck may hold [default: 1].
+    --max-deck N        Most cards a deck may hold [default: 60].
+    --max-copies N      Most copies of one card a deck may hold
+                        [default: 60].
     --verbose           Emit log output.
     --version, -v       Show tool version.
     --help, -h          Show this screen.
@@ -442,6 +660,7 @@ Options:
 struct Args {
     cmd_echo: bool,
     cmd_http: bool,
+    cmd_game: bool,
     flag_port: Option<u16>,
     flag_verbose: bool,
     flag_protover: Vec<String>,
@@ -452,8 +671,17 @@ struct Args {
     flag_ocsp: Option<String>,
     flag_auth: Option<String>,
     flag_require_auth: bool,
+    flag_players: Option<String>,
     flag_resumption: bool,
     flag_tickets: bool,
+    flag_cards: Option<String>,
+    flag_trait: Vec<String>,
+    flag_refill: Vec<String>,
+    flag_defeat: Option<String>,
+    flag_seed: u64,
+    flag_min_deck: usize,
+    flag_max_deck: usize,
+    flag_max_copies: usize,
     arg_fport: Option<u16>,
 }
 
@@ -622,6 +850,11 @@ fn main() {
         .and_then(|d| d.deserialize())
         .unwrap_or_else(|e| e.exit());
 
+    // Without client certificates, nobody can be held to the allowlist.
+    if args.flag_players.is_some() && args.flag_auth.is_none() {
+        panic!("--players needs --auth to check client certificates");
+    }
+
     if args.flag_verbose {
         env_logger::Builder::new()
             .parse_filters("trace")
@@ -644,11 +877,38 @@ fn main() {
         ServerMode::Echo
     } else if args.cmd_http {
         ServerMode::Http
+    } else if args.cmd_game {
+        ServerMode::Game
     } else {
         ServerMode::Forward(args.arg_fport.expect("fport required"))
     };
 
-    let mut tlsserv = TlsServer::new(listener, mode, config);
+    let lobby = if args.cmd_game {
+        let rules = load_rules(
+            Path::new(args.flag_cards.as_ref().expect("--cards option missing")),
+            &args.flag_trait,
+            &args.flag_refill,
+            args.flag_defeat.as_deref(),
+        )
+        .unwrap_or_else(|e| panic!("cannot load rules: {}", e));
+        let deck_rules = DeckRules::new(
+            args.flag_min_deck,
+            args.flag_max_deck,
+            args.flag_max_copies,
+        );
+        Some(Lobby::new(rules, deck_rules, args.flag_seed))
+    } else {
+        None
+    };
+
+    let identities = args.flag_auth.as_ref().map(|_| {
+        Arc::new(match &args.flag_players {
+            Some(file) => Identities::load(file),
+            None => Identities::Fingerprint,
+        })
+    });
+
+    let mut tlsserv = TlsServer::new(listener, mode, config, lobby, identities);
 
     let mut events = mio::Events::with_capacity(256);
     loop {
@@ -665,3 +925,256 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use kier::protocol::RoomInfo;
+    use kier::{effect, Card, EndCondition, Rules, Targets, Trait};
+
+    use super::*;
+
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn server_config() -> Arc<rustls::ServerConfig> {
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                load_certs(&testdata("server.pem")),
+                load_private_key(&testdata("server.key")),
+            )
+            .unwrap();
+        Arc::new(config)
+    }
+
+    fn client() -> rustls::ClientConnection {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&testdata("ca.pem")) {
+            roots.add(&cert).unwrap();
+        }
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let name = "localhost".try_into().unwrap();
+        rustls::ClientConnection::new(Arc::new(config), name).unwrap()
+    }
+
+    /// A game-mode connection on `token`, registered with `registry`,
+    /// and the client it has completed a handshake with in memory.
+    fn connect(
+        registry: &mio::Registry,
+        token: mio::Token,
+    ) -> (OpenConnection, rustls::ClientConnection) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let tls_conn = rustls::ServerConnection::new(server_config()).unwrap();
+        let mut conn = OpenConnection::new(
+            TcpStream::from_std(socket),
+            token,
+            ServerMode::Game,
+            tls_conn,
+            None,
+        );
+        conn.register(registry);
+
+        let mut client = client();
+        while client.is_handshaking() || conn.tls_conn.is_handshaking() {
+            pump(&mut client, &mut conn.tls_conn);
+        }
+        (conn, client)
+    }
+
+    /// Moves pending TLS records each way between `client` and `server`.
+    fn pump(client: &mut rustls::ClientConnection, server: &mut rustls::ServerConnection) {
+        let mut records = Vec::new();
+        client.write_tls(&mut records).unwrap();
+        let mut rd = &records[..];
+        while !rd.is_empty() {
+            server.read_tls(&mut rd).unwrap();
+        }
+        server.process_new_packets().unwrap();
+
+        let mut records = Vec::new();
+        while server.wants_write() {
+            server.write_tls(&mut records).unwrap();
+        }
+        let mut rd = &records[..];
+        while !rd.is_empty() {
+            client.read_tls(&mut rd).unwrap();
+        }
+        client.process_new_packets().unwrap();
+    }
+
+    /// Everything the server has sent `client` since the last call.
+    fn responses(
+        conn: &mut OpenConnection,
+        client: &mut rustls::ClientConnection,
+    ) -> Vec<Response> {
+        pump(client, &mut conn.tls_conn);
+        let mut plain = Vec::new();
+        // The reader reports an error once it has run dry, after
+        // handing over everything it had.
+        let _ = client.reader().read_to_end(&mut plain);
+
+        let mut out = Vec::new();
+        let mut used = 0;
+        while let Some((response, len)) = protocol::decode(&plain[used..]).unwrap() {
+            out.push(response);
+            used += len;
+        }
+        assert_eq!(used, plain.len());
+        out
+    }
+
+    fn frames(requests: &[Request]) -> Vec<u8> {
+        requests.iter()
+            .flat_map(|request| protocol::encode(request).unwrap())
+            .collect()
+    }
+
+    fn hello() -> Request {
+        Request::Hello {
+            version: PROTOCOL_VERSION,
+        }
+    }
+
+    fn lobby() -> Lobby {
+        let mut strike = Card::new("Strike", "", effect::no_effect);
+        strike.targets = Targets::Enemy;
+        let mut rules = Rules::new(Arc::new(vec![strike]), Arc::new(Vec::new()));
+        let mut health = Trait::new("health");
+        health.default = 20;
+        rules.trait_list = Arc::new(vec![health]);
+        rules.end_conditions.push(EndCondition::Defeat { id: 0 });
+        Lobby::new(rules, DeckRules::new(1, 60, 60), 1)
+    }
+
+    #[test]
+    fn decodes_whole_frames() {
+        let poll = mio::Poll::new().unwrap();
+        let (mut conn, mut client) = connect(poll.registry(), mio::Token(2));
+        let bytes = frames(&[hello(), Request::ListRooms, Request::Ready]);
+
+        // Nothing is decoded until the frame holding it is complete.
+        conn.incoming.extend_from_slice(&bytes[..3]);
+        conn.decode_requests();
+        assert!(!conn.greeted);
+        conn.incoming.extend_from_slice(&bytes[3..bytes.len() - 1]);
+        conn.decode_requests();
+        assert_eq!(conn.requests, vec![Request::ListRooms]);
+        conn.incoming.extend_from_slice(&bytes[bytes.len() - 1..]);
+        conn.decode_requests();
+        assert_eq!(conn.requests, vec![Request::ListRooms, Request::Ready]);
+        assert!(conn.incoming.is_empty());
+
+        assert_eq!(
+            responses(&mut conn, &mut client),
+            vec![Response::Welcome {
+                version: PROTOCOL_VERSION,
+            }]
+        );
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let poll = mio::Poll::new().unwrap();
+        let (mut conn, mut client) = connect(poll.registry(), mio::Token(2));
+        conn.incoming = frames(&[
+            Request::Hello {
+                version: PROTOCOL_VERSION + 1,
+            },
+            Request::ListRooms,
+        ]);
+        conn.decode_requests();
+        assert!(!conn.greeted);
+        assert!(conn.requests.is_empty());
+        assert!(conn.incoming.is_empty());
+        assert_eq!(
+            responses(&mut conn, &mut client),
+            vec![Response::Error(ProtocolError::VersionMismatch {
+                server: PROTOCOL_VERSION,
+                client: PROTOCOL_VERSION + 1,
+            })]
+        );
+    }
+
+    #[test]
+    fn refuses_sessions_without_hello() {
+        let poll = mio::Poll::new().unwrap();
+        let (mut conn, mut client) = connect(poll.registry(), mio::Token(2));
+        conn.incoming = frames(&[Request::ListRooms, hello()]);
+        conn.decode_requests();
+        assert!(!conn.greeted);
+        assert_eq!(
+            responses(&mut conn, &mut client),
+            vec![Response::Error(ProtocolError::NoHandshake)]
+        );
+
+        let (mut conn, mut client) = connect(poll.registry(), mio::Token(3));
+        conn.incoming = vec![1, 0, 0, 0, 0xff];
+        conn.decode_requests();
+        assert!(matches!(
+            responses(&mut conn, &mut client)[..],
+            [Response::Error(ProtocolError::Malformed(_))]
+        ));
+    }
+
+    #[test]
+    fn routes_and_delivers_to_each_session() {
+        let poll = mio::Poll::new().unwrap();
+        let registry = poll.registry();
+        let listener = TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let mut server = TlsServer::new(
+            listener,
+            ServerMode::Game,
+            server_config(),
+            Some(lobby()),
+            None,
+        );
+
+        let mut clients = Vec::new();
+        for id in 1..=2 {
+            let token = mio::Token(id as usize + 1);
+            let (mut conn, mut client) = connect(registry, token);
+            conn.incoming = frames(&[hello(), Request::Join { player: id }]);
+            conn.decode_requests();
+            server.connections.insert(token, conn);
+            server.route_requests(registry, token);
+
+            let conn = server.connections.get_mut(&token).unwrap();
+            assert_eq!(conn.player, Some(id));
+            let out = responses(conn, &mut client);
+            assert!(matches!(out[..], [Response::Welcome { .. }, Response::Rooms(_)]));
+            clients.push((token, client));
+        }
+
+        let send = |server: &mut TlsServer, token: mio::Token, requests: &[Request]| {
+            let conn = server.connections.get_mut(&token).unwrap();
+            conn.incoming = frames(requests);
+            conn.decode_requests();
+            server.route_requests(registry, token);
+        };
+        let join = Request::JoinRoom {
+            room: 1,
+            deck: "6 Strike".to_string(),
+        };
+        let create = Request::CreateRoom { seats: 2 };
+        send(&mut server, clients[0].0, &[create, join.clone(), Request::Ready]);
+        send(&mut server, clients[1].0, &[join, Request::Ready]);
+
+        // The last ready player's request starts the game for both.
+        for (token, client) in clients.iter_mut() {
+            let conn = server.connections.get_mut(token).unwrap();
+            let out = responses(conn, client);
+            assert!(out.iter().any(|response| matches!(
+                response,
+                Response::Room(RoomInfo { started: true, .. })
+            )));
+            assert!(matches!(out.last(), Some(Response::State(_))));
+        }
+    }
+}
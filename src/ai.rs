@@ -0,0 +1,385 @@
+use crate::{
+    CharacterIdx, Command, EndCondition, Encounter, Game, Phase, Rng,
+    Targets,
+};
+
+// Controllers only see a read-only encounter and answer with the command
+// the character should take; features are left to the players.
+pub trait Controller {
+    fn decide(
+        &mut self,
+        encounter: &Encounter,
+        character: CharacterIdx
+    ) -> Command;
+}
+
+// A bound on the commands a controlled character may take in one turn,
+// so that a controller that never ends its turn cannot hang the game.
+pub const MAX_TURN_ACTIONS: usize = 256;
+
+// How good the encounter looks for `side`. A decided encounter is worth
+// far more than any position; otherwise it is the total of every
+// `Defeat` trait on `side` less the total on the other sides.
+pub fn score(encounter: &Encounter, side: usize) -> i64 {
+    const WIN: i64 = i64::MAX / 2;
+    if let Some(outcome) = &encounter.outcome {
+        return match outcome.winner {
+            Some(winner) if winner == side => WIN,
+            Some(_) => -WIN,
+            None => 0,
+        };
+    }
+
+    let mut total: i64 = 0;
+    for condition in encounter.end_conditions.iter() {
+        if let EndCondition::Defeat { id } = condition {
+            for (cid, character) in encounter.characters.iter().enumerate() {
+                let value = encounter.get_trait(cid, *id);
+                if character.side == side {
+                    total = total.saturating_add(value);
+                } else {
+                    total = total.saturating_sub(value);
+                }
+            }
+        }
+    }
+    total
+}
+
+impl Encounter {
+    // The commands `actor()` can take: every affordable hand card on
+    // each of its legal targets, then ending the turn. In a response
+    // window only reactions are listed, followed by passing. Cards that
+    // target nothing or all enemies are listed once.
+    pub fn legal_moves(&self) -> Vec::<Command> {
+        let mut moves = Vec::new();
+        if self.done || self.phase != Phase::Main {
+            return moves;
+        }
+
+        let pid = self.actor();
+        let responding = self.window().is_some();
+        let deck = &self.characters[pid].deck;
+        for (index, &cid) in deck.hand.iter().enumerate() {
+            let cd = match deck.clist.get(cid as usize) {
+                Some(&Some(n)) => match self.card_list.get(n as usize) {
+                    Some(cd) => cd,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if (responding && !cd.reaction)
+                || self.check_cost(pid, &cd.cost).is_err()
+            {
+                continue;
+            }
+            let targets = match cd.targets {
+                Targets::None => vec![pid],
+                Targets::AllEnemies => self.legal_targets(pid, index)
+                    .into_iter()
+                    .take(1)
+                    .collect(),
+                _ => self.legal_targets(pid, index),
+            };
+            for target in targets {
+                moves.push(if responding {
+                    Command::Respond { character: pid, target, index }
+                } else {
+                    Command::PlayCard { character: pid, target, index }
+                });
+            }
+        }
+        if responding {
+            for feature in 0..self.features.len() {
+                if self.check_reaction(pid, feature).is_ok() {
+                    moves.push(Command::RespondFeature {
+                        character: pid,
+                        feature,
+                    });
+                }
+            }
+        }
+        moves.push(self.yield_command());
+
+        moves
+    }
+
+    // Passing in a response window, or else ending the turn.
+    pub fn yield_command(&self) -> Command {
+        match self.window() {
+            Some(window) => Command::Pass {
+                character: window.waiting[0],
+            },
+            None => Command::EndTurn,
+        }
+    }
+
+    fn try_command(&mut self, command: Command) -> bool {
+        self.execute(command).is_ok()
+    }
+
+    // Replaces what `side` cannot see with a guess drawn from `rng`:
+    // every draw pile is shuffled, and the other sides' hands are dealt
+    // afresh from their hand and draw pile together.
+    fn determinize(&mut self, side: usize, rng: &mut Rng) {
+        for character in self.characters.iter_mut() {
+            let deck = &mut character.deck;
+            if character.side != side {
+                let size = deck.hand.len();
+                deck.deck.append(&mut deck.hand);
+                rng.shuffle(&mut deck.deck);
+                let rest = deck.deck.split_off(size);
+                deck.hand = std::mem::replace(&mut deck.deck, rest);
+            } else {
+                rng.shuffle(&mut deck.deck);
+            }
+        }
+    }
+}
+
+// Plays whichever card leaves the best score after it resolves, and
+// ends the turn or passes once every card would make things worse.
+pub struct Greedy {
+    pub score: fn(&Encounter, usize) -> i64,
+}
+
+impl Greedy {
+    pub fn new() -> Self {
+        Greedy { score }
+    }
+}
+
+impl Default for Greedy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Controller for Greedy {
+    fn decide(
+        &mut self,
+        encounter: &Encounter,
+        character: CharacterIdx
+    ) -> Command {
+        let side = encounter.characters[character].side;
+        let mut best = (self.score)(encounter, side);
+        let yielded = encounter.yield_command();
+        let mut choice = yielded;
+        for command in encounter.legal_moves() {
+            if command == yielded {
+                continue;
+            }
+            let mut sim = encounter.clone();
+            if !sim.try_command(command) {
+                continue;
+            }
+            let value = (self.score)(&sim, side);
+            if value >= best {
+                best = value;
+                choice = command;
+            }
+        }
+        choice
+    }
+}
+
+struct Node {
+    command: Command,
+    // The side of the character that took `command`, whose point of
+    // view the node's reward is kept from.
+    side: usize,
+    visits: u32,
+    reward: f64,
+    children: Vec::<usize>,
+    untried: Vec::<Command>,
+}
+
+// Monte-Carlo tree search with UCB1 selection and random rollouts, in
+// which characters play random cards and end their turn only once they
+// have none left to play. Each iteration searches a copy of the
+// encounter determinized from `rng`: draw piles are reshuffled and the
+// other sides' hands redealt, so the search never relies on the real
+// order of decks or on cards its side cannot see.
+pub struct Mcts {
+    pub iterations: usize,
+    pub exploration: f64,
+    // Commands taken in a rollout before the position is scored.
+    pub depth: usize,
+    pub score: fn(&Encounter, usize) -> i64,
+    pub rng: Rng,
+}
+
+impl Mcts {
+    pub fn new(seed: u64) -> Self {
+        Mcts {
+            iterations: 1000,
+            exploration: std::f64::consts::SQRT_2,
+            depth: 64,
+            score,
+            rng: Rng::new(seed),
+        }
+    }
+
+    // Maps a score to a reward in [0, 1] for `side`.
+    fn reward(&self, encounter: &Encounter, side: usize) -> f64 {
+        if let Some(outcome) = &encounter.outcome {
+            return match outcome.winner {
+                Some(winner) if winner == side => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+        }
+
+        let value = (self.score)(encounter, side) as f64;
+        0.5 + 0.5 * value / (value.abs() + 10.0)
+    }
+
+    fn pick(&mut self, commands: &[Command]) -> Command {
+        commands[self.rng.below(commands.len() as u64) as usize]
+    }
+
+    fn select(&self, tree: &[Node], node: usize) -> usize {
+        let parent = tree[node].visits.max(1) as f64;
+        let ucb = |child: &Node| {
+            let visits = child.visits.max(1) as f64;
+            child.reward / visits
+                + self.exploration * (parent.ln() / visits).sqrt()
+        };
+        let mut best = tree[node].children[0];
+        for &child in tree[node].children.iter() {
+            if ucb(&tree[child]) > ucb(&tree[best]) {
+                best = child;
+            }
+        }
+        best
+    }
+
+    fn iterate(&mut self, tree: &mut Vec::<Node>, encounter: &Encounter) {
+        let mut sim = encounter.clone();
+        sim.rng = Rng::new(self.rng.next_u64());
+        sim.determinize(tree[0].side, &mut self.rng);
+
+        let mut path = vec![0];
+        let mut node = 0;
+        while tree[node].untried.is_empty()
+            && !tree[node].children.is_empty()
+            && !sim.done
+        {
+            node = self.select(tree, node);
+            path.push(node);
+            if !sim.try_command(tree[node].command) {
+                break;
+            }
+        }
+
+        if !sim.done && !tree[node].untried.is_empty() {
+            let i = self.rng.below(tree[node].untried.len() as u64);
+            let command = tree[node].untried.swap_remove(i as usize);
+            let side = sim.characters[sim.actor()].side;
+            if sim.try_command(command) {
+                tree.push(Node {
+                    command,
+                    side,
+                    visits: 0,
+                    reward: 0.0,
+                    children: Vec::new(),
+                    untried: sim.legal_moves(),
+                });
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                path.push(child);
+            }
+        }
+
+        for _ in 0..self.depth {
+            let mut moves = sim.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            if moves.len() > 1 {
+                moves.pop();
+            }
+            let command = self.pick(&moves);
+            sim.try_command(command);
+        }
+
+        for &n in path.iter() {
+            let reward = self.reward(&sim, tree[n].side);
+            tree[n].visits += 1;
+            tree[n].reward += reward;
+        }
+    }
+}
+
+impl Controller for Mcts {
+    fn decide(
+        &mut self,
+        encounter: &Encounter,
+        character: CharacterIdx
+    ) -> Command {
+        let mut tree = vec![Node {
+            command: Command::EndTurn,
+            side: encounter.characters[character].side,
+            visits: 0,
+            reward: 0.0,
+            children: Vec::new(),
+            untried: encounter.legal_moves(),
+        }];
+        for _ in 0..self.iterations {
+            self.iterate(&mut tree, encounter);
+        }
+
+        tree[0].children.iter()
+            .max_by_key(|&&child| tree[child].visits)
+            .map(|&child| tree[child].command)
+            .unwrap_or(encounter.yield_command())
+    }
+}
+
+impl Game {
+    pub fn set_controller(
+        &mut self,
+        cid: CharacterIdx,
+        controller: Box::<dyn Controller>
+    ) {
+        self.controllers.insert(cid, controller);
+    }
+
+    // Lets controlled characters act until an uncontrolled character is
+    // to act or the encounter ends. A rejected command ends the
+    // character's turn, or passes in a response window. Returns the
+    // number of commands applied.
+    pub fn run_controllers(&mut self) -> usize {
+        self.run_controllers_until(u64::MAX)
+    }
+
+    // As `run_controllers`, but also stops once round `last` is over.
+    pub fn run_controllers_until(&mut self, last: u64) -> usize {
+        let mut count = 0;
+        let mut taken = 0;
+        let mut turn = (self.encounter.active, self.encounter.round);
+        while !self.encounter.done && self.encounter.round <= last {
+            if (self.encounter.active, self.encounter.round) != turn {
+                turn = (self.encounter.active, self.encounter.round);
+                taken = 0;
+            }
+            let actor = self.encounter.actor();
+            let controller = match self.controllers.get_mut(&actor) {
+                Some(controller) => controller,
+                None => break,
+            };
+            let fallback = self.encounter.yield_command();
+            let command = if taken < MAX_TURN_ACTIONS {
+                controller.decide(&self.encounter, actor)
+            } else {
+                fallback
+            };
+            taken += 1;
+            if self.apply(command).is_err() && self.apply(fallback).is_err() {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+}
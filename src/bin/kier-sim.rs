@@ -0,0 +1,124 @@
+use std::io;
+use std::path::Path;
+use std::process;
+
+use docopt::Docopt;
+
+#[macro_use]
+extern crate serde_derive;
+
+use kier::ai::{Controller, Greedy, Mcts};
+use kier::loader::{load_deck, load_rules};
+use kier::sim::Simulation;
+use kier::{Character, Deck, Player};
+
+const USAGE: &str = "
+Plays encounters between decks with AI controllers and reports how each
+side and each card fared.  Every deck is one character on its own side,
+in the order given.  `--trait' declares a trait that cards can name as
+NAME=DEFAULT, and `--refill' resets a trait at the start of each turn as
+NAME=VALUE.  A side loses once every character on it has the `--defeat'
+trait, by default the first trait, at zero or below.
+
+Usage:
+  kier-sim --cards CARDFILE (--deck DECKFILE)... [--trait SPEC]... \
+     [--refill SPEC]... [--ai AI]... [options]
+  kier-sim (--version | -v)
+  kier-sim (--help | -h)
+
+Options:
+    --cards CARDFILE    Read card definitions from CARDFILE.
+    --deck DECKFILE     Read a deck list from DECKFILE.
+    --trait SPEC        Declare a trait as NAME=DEFAULT.
+    --refill SPEC       Refill a trait each turn as NAME=VALUE.
+    --defeat NAME       Trait that decides defeat.
+    --ai AI             Controller for the next deck: greedy or mcts.
+                        Decks without one play greedily.
+    --iterations N      Search iterations for mcts [default: 200].
+    --games N           Number of encounters to play [default: 1000].
+    --seed N            Seed for the batch [default: 0].
+    --rounds N          Stop an encounter after N rounds [default: 100].
+    --format FORMAT     Report as csv or json [default: csv].
+    --version, -v       Show tool version.
+    --help, -h          Show this screen.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    flag_cards: String,
+    flag_deck: Vec<String>,
+    flag_trait: Vec<String>,
+    flag_refill: Vec<String>,
+    flag_defeat: Option<String>,
+    flag_ai: Vec<String>,
+    flag_iterations: usize,
+    flag_games: usize,
+    flag_seed: u64,
+    flag_rounds: u64,
+    flag_format: String,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("kier-sim: {}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let version = env!("CARGO_PKG_NAME").to_string() + ", version: "
+        + env!("CARGO_PKG_VERSION");
+
+    let args: Args = Docopt::new(USAGE)
+        .map(|d| d.help(true))
+        .map(|d| d.version(Some(version)))
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let rules = load_rules(
+        Path::new(&args.flag_cards),
+        &args.flag_trait,
+        &args.flag_refill,
+        args.flag_defeat.as_deref()
+    ).unwrap_or_else(|e| fail(&e.to_string()));
+    let mut players = Vec::new();
+    for (side, file) in args.flag_deck.iter().enumerate() {
+        let list = load_deck(Path::new(file), &rules.card_list)
+            .unwrap_or_else(|e| fail(&e.to_string()));
+        let mut character = Character::new(
+            Default::default(),
+            Deck::new(&list)
+        );
+        character.side = side;
+        players.push(Player {
+            id: side as u64,
+            character,
+        });
+    }
+
+    let mut sim = Simulation::new(players, args.flag_games, args.flag_seed);
+    sim.max_rounds = args.flag_rounds;
+    for ai in args.flag_ai.iter() {
+        let iterations = args.flag_iterations;
+        sim.controllers.push(match ai.as_str() {
+            "greedy" => Box::new(|_| Box::new(Greedy::new())),
+            "mcts" => Box::new(move |seed| {
+                let mut mcts = Mcts::new(seed);
+                mcts.iterations = iterations;
+                Box::new(mcts) as Box<dyn Controller>
+            }),
+            other => fail(&format!("unknown ai '{}'", other)),
+        });
+    }
+    let report = sim.run(&rules);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = match args.flag_format.as_str() {
+        "csv" => report.write_csv(&mut out),
+        "json" => serde_json::to_writer_pretty(&mut out, &report)
+            .map_err(io::Error::from),
+        other => fail(&format!("unknown format '{}'", other)),
+    };
+    if let Err(e) = result {
+        fail(&e.to_string());
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt;
+
+use crate::{
+    CardID, Character, CharacterIdx, Deck, Encounter, FeatureID, Game,
+    Player, Refill, Rng, Rules, TraitID, TraitValue,
+};
+
+// Bump this whenever the layout of `Run` changes, as for snapshots.
+pub const RUN_VERSION: u32 = 1;
+
+pub type NodeIdx = usize;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Offer {
+    pub card: CardID,
+    pub price: TraitValue,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum NodeKind {
+    // A fight between the party and `enemies`, with the party's
+    // characters first.
+    Encounter {
+        enemies: Vec::<Player>,
+        features: Vec::<FeatureID>,
+    },
+    // One of `choices` may be added to a character's deck.
+    Reward {
+        choices: Vec::<CardID>,
+    },
+    // Cards bought with the `currency` trait of the buyer.
+    Shop {
+        currency: TraitID,
+        offers: Vec::<Offer>,
+    },
+    // Restores trait `id` of every party character.
+    Rest {
+        id: TraitID,
+        refill: Refill,
+    },
+}
+
+// A node of the run's map. Nodes without `next` end the run once they
+// are cleared, and nodes with several let the party pick a branch.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub next: Vec::<NodeIdx>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RunStatus {
+    InProgress,
+    Won,
+    Lost,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunError {
+    UnsupportedVersion(u32),
+    // The map has no node at this index, yet the run refers to one.
+    MissingNode(NodeIdx),
+    // The game was not started from the run's current node.
+    ForeignGame,
+    RunOver,
+    WrongNode,
+    NodeNotCleared,
+    NodeCleared,
+    NoSuchNode(NodeIdx),
+    NoSuchCharacter(CharacterIdx),
+    NoSuchChoice(usize),
+    EncounterNotOver,
+    CannotAfford {
+        price: TraitValue,
+        available: TraitValue,
+    },
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::UnsupportedVersion(v) =>
+                write!(f, "unsupported run version {}", v),
+            RunError::MissingNode(node) =>
+                write!(f, "the map has no node {}", node),
+            RunError::ForeignGame =>
+                write!(f, "the game was not started from this node"),
+            RunError::RunOver => write!(f, "the run is over"),
+            RunError::WrongNode =>
+                write!(f, "the current node does not allow that"),
+            RunError::NodeNotCleared =>
+                write!(f, "the current node is not cleared"),
+            RunError::NodeCleared =>
+                write!(f, "the current node is already cleared"),
+            RunError::NoSuchNode(node) =>
+                write!(f, "node {} is not reachable from here", node),
+            RunError::NoSuchCharacter(cid) =>
+                write!(f, "no party character {}", cid),
+            RunError::NoSuchChoice(i) => write!(f, "no choice {}", i),
+            RunError::EncounterNotOver =>
+                write!(f, "the encounter has not ended"),
+            RunError::CannotAfford { price, available } => write!(
+                f, "costs {}, but only {} is available", price, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+// A campaign across a map of nodes. The party's characters keep their
+// traits and deck lists from one node to the next, while piles, hands
+// and statuses are reset after every encounter. The whole run
+// serializes, so it can be saved between nodes and resumed later.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub version: u32,
+    pub map: Vec::<Node>,
+    pub party: Vec::<Player>,
+    pub position: NodeIdx,
+    pub cleared: bool,
+    pub status: RunStatus,
+    pub visited: Vec::<NodeIdx>,
+    pub seed: u64,
+}
+
+impl Run {
+    pub fn new(
+        map: Vec::<Node>,
+        start: NodeIdx,
+        party: Vec::<Player>,
+        seed: u64
+    ) -> Result<Self, RunError> {
+        let mut run = Run {
+            version: RUN_VERSION,
+            map,
+            party,
+            position: start,
+            cleared: false,
+            status: RunStatus::InProgress,
+            visited: vec![start],
+            seed,
+        };
+        run.check_map()?;
+        run.arrive();
+        Ok(run)
+    }
+
+    // Checks a deserialized run before it is played on: its version and
+    // every node index in it.
+    pub fn resume(self) -> Result<Self, RunError> {
+        if self.version == 0 || self.version > RUN_VERSION {
+            return Err(RunError::UnsupportedVersion(self.version));
+        }
+        self.check_map()?;
+        Ok(self)
+    }
+
+    fn check_map(&self) -> Result<(), RunError> {
+        let next = self.map.iter().flat_map(|node| node.next.iter());
+        for &node in next.chain(&self.visited).chain([&self.position]) {
+            if node >= self.map.len() {
+                return Err(RunError::MissingNode(node));
+            }
+        }
+        Ok(())
+    }
+
+    // Runs from `new` and `resume` always have a current node.
+    pub fn current(&self) -> &Node {
+        &self.map[self.position]
+    }
+
+    fn check(&self, cleared: bool) -> Result<(), RunError> {
+        if self.status != RunStatus::InProgress {
+            return Err(RunError::RunOver);
+        }
+        match (self.cleared, cleared) {
+            (true, false) => Err(RunError::NodeCleared),
+            (false, true) => Err(RunError::NodeNotCleared),
+            _ => Ok(()),
+        }
+    }
+
+    fn character(
+        &mut self,
+        cid: CharacterIdx
+    ) -> Result<&mut Character, RunError> {
+        self.party.get_mut(cid)
+            .map(|player| &mut player.character)
+            .ok_or(RunError::NoSuchCharacter(cid))
+    }
+
+    // Shops never hold the party up: they count as cleared on arrival,
+    // and stay open for buying until the party advances.
+    fn arrive(&mut self) {
+        if matches!(self.current().kind, NodeKind::Shop { .. }) {
+            self.clear();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.cleared = true;
+        if self.current().next.is_empty() {
+            self.status = RunStatus::Won;
+        }
+    }
+
+    // Starts the fight at the current node. Each node gets its own seed,
+    // so starting the same fight again replays it exactly.
+    pub fn start_encounter(&self, rules: &Rules) -> Result<Game, RunError> {
+        self.check(false)?;
+        let (enemies, features) = match &self.current().kind {
+            NodeKind::Encounter { enemies, features } => (enemies, features),
+            _ => return Err(RunError::WrongNode),
+        };
+
+        let mut players = self.party.clone();
+        players.extend(enemies.iter().cloned());
+        Ok(Game::new(players, features.clone(), rules, self.node_seed()))
+    }
+
+    fn node_seed(&self) -> u64 {
+        Rng::new(self.seed.wrapping_add(self.visited.len() as u64)).next_u64()
+    }
+
+    // Carries the party out of a finished fight, which must be the game
+    // `start_encounter` set up for the current node. The run is lost
+    // unless the party's side won.
+    pub fn finish_encounter(&mut self, game: &Game) -> Result<(), RunError> {
+        self.check(false)?;
+        let enemies = match &self.current().kind {
+            NodeKind::Encounter { enemies, .. } => enemies,
+            _ => return Err(RunError::WrongNode),
+        };
+        let setup = &game.recording.setup;
+        let ids = self.party.iter().chain(enemies).map(|p| p.id);
+        if setup.seed != self.node_seed()
+            || !ids.eq(setup.players.iter().map(|p| p.id))
+            || game.encounter.characters.len() != setup.players.len()
+        {
+            return Err(RunError::ForeignGame);
+        }
+        let outcome = game.outcome().ok_or(RunError::EncounterNotOver)?;
+
+        let side = self.party.first().map(|p| p.character.side);
+        if side.is_none() || outcome.winner != side {
+            self.status = RunStatus::Lost;
+            return Ok(());
+        }
+        for (cid, player) in self.party.iter_mut().enumerate() {
+            let mut character = game.encounter.characters[cid].clone();
+            let slots: Vec::<CardID> = (0..character.deck.clist.len())
+                .filter(|&i| character.deck.clist[i].is_some())
+                .map(|i| i as CardID)
+                .collect();
+            character.deck.deck = slots;
+            character.deck.hand.clear();
+            character.deck.discard.clear();
+            character.statuses.clear();
+            player.character = character;
+        }
+        self.clear();
+        Ok(())
+    }
+
+    // Adds reward `choice` to a character's deck, or with `None` passes
+    // on the reward.
+    pub fn take_reward(
+        &mut self,
+        cid: CharacterIdx,
+        choice: Option<usize>
+    ) -> Result<(), RunError> {
+        self.check(false)?;
+        let card = match (&self.current().kind, choice) {
+            (NodeKind::Reward { .. }, None) => None,
+            (NodeKind::Reward { choices }, Some(i)) => Some(
+                *choices.get(i).ok_or(RunError::NoSuchChoice(i))?
+            ),
+            _ => return Err(RunError::WrongNode),
+        };
+
+        let character = self.character(cid)?;
+        if let Some(card) = card {
+            add_card(&mut character.deck, card);
+        }
+        self.clear();
+        Ok(())
+    }
+
+    // Buys an offer for a character, paying from its stored currency
+    // trait. A shop at the end of the map wins the run on arrival, but
+    // stays open for buying.
+    pub fn buy(
+        &mut self,
+        rules: &Rules,
+        cid: CharacterIdx,
+        offer: usize
+    ) -> Result<(), RunError> {
+        if self.status == RunStatus::Lost {
+            return Err(RunError::RunOver);
+        }
+        let (currency, card, price) = match &self.current().kind {
+            NodeKind::Shop { currency, offers } => {
+                let o = offers.get(offer)
+                    .ok_or(RunError::NoSuchChoice(offer))?;
+                (*currency, o.card, o.price)
+            }
+            _ => return Err(RunError::WrongNode),
+        };
+
+        let character = self.character(cid)?;
+        let available = stored(character, rules, currency);
+        if available < price {
+            return Err(RunError::CannotAfford { price, available });
+        }
+        character.traits.insert(currency, available - price);
+        add_card(&mut character.deck, card);
+        if let NodeKind::Shop { offers, .. } =
+            &mut self.map[self.position].kind
+        {
+            offers.remove(offer);
+        }
+        Ok(())
+    }
+
+    // Restores the rest node's trait on every party character, clamped
+    // to the trait's bounds in `rules`. A trait refilled to another is
+    // read as an encounter would read it, so maximums derived from other
+    // traits work between nodes too.
+    pub fn rest(&mut self, rules: &Rules) -> Result<(), RunError> {
+        self.check(false)?;
+        let (id, refill) = match self.current().kind {
+            NodeKind::Rest { id, refill } => (id, refill),
+            _ => return Err(RunError::WrongNode),
+        };
+
+        let characters = self.party.iter()
+            .map(|player| player.character.clone())
+            .collect();
+        let party = Encounter::new(characters, Vec::new(), rules, self.seed);
+        for (cid, player) in self.party.iter_mut().enumerate() {
+            let character = &mut player.character;
+            let value = match refill {
+                Refill::Set(value) => value,
+                Refill::Add(amount) => {
+                    stored(character, rules, id).saturating_add(amount)
+                }
+                Refill::ToTrait(other) => party.base_trait(cid, other),
+            };
+            let def = rules.trait_list.get(id as usize);
+            let value = def.map_or(value, |t| t.clamp(value));
+            character.traits.insert(id, value);
+        }
+        self.clear();
+        Ok(())
+    }
+
+    // Moves on to `next`, one of the current node's successors, once
+    // the current node is cleared.
+    pub fn advance(&mut self, next: NodeIdx) -> Result<(), RunError> {
+        self.check(true)?;
+        if !self.current().next.contains(&next) || next >= self.map.len() {
+            return Err(RunError::NoSuchNode(next));
+        }
+
+        self.position = next;
+        self.cleared = false;
+        self.visited.push(next);
+        self.arrive();
+        Ok(())
+    }
+}
+
+// A trait as carried between nodes: the stored value, or else the
+// trait's default in `rules`.
+fn stored(character: &Character, rules: &Rules, id: TraitID) -> TraitValue {
+    character.traits.get(&id).copied().unwrap_or(
+        rules.trait_list.get(id as usize).map_or(0, |t| t.default)
+    )
+}
+
+fn add_card(deck: &mut Deck, card: CardID) {
+    deck.deck.push(deck.clist.len() as CardID);
+    deck.clist.push(Some(card));
+}
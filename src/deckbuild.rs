@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Card, CardID, Deck};
+
+// Limits on the lists players may build decks from. A deck is built for
+// a class, or for none: it may hold neutral cards and cards of its own
+// class, minus anything in `banned` or in that class's entry of
+// `class_banned`.
+#[derive(Clone, Debug)]
+pub struct DeckRules {
+    pub min_size: usize,
+    pub max_size: usize,
+    pub max_copies: usize,
+    pub banned: Vec::<CardID>,
+    pub class_banned: HashMap::<String, Vec::<CardID>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeckViolation {
+    TooFew {
+        size: usize,
+        min: usize,
+    },
+    TooMany {
+        size: usize,
+        max: usize,
+    },
+    TooManyCopies {
+        card: CardID,
+        count: usize,
+        max: usize,
+    },
+    UnknownCard(CardID),
+    Banned(CardID),
+    WrongClass {
+        card: CardID,
+        class: String,
+    },
+}
+
+impl fmt::Display for DeckViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeckViolation::TooFew { size, min } => write!(
+                f, "deck has {} cards, fewer than the minimum of {}",
+                size, min
+            ),
+            DeckViolation::TooMany { size, max } => write!(
+                f, "deck has {} cards, more than the maximum of {}",
+                size, max
+            ),
+            DeckViolation::TooManyCopies { card, count, max } => write!(
+                f, "deck has {} copies of card {}, more than {}",
+                count, card, max
+            ),
+            DeckViolation::UnknownCard(card) =>
+                write!(f, "unknown card {}", card),
+            DeckViolation::Banned(card) =>
+                write!(f, "card {} is banned", card),
+            DeckViolation::WrongClass { card, class } => write!(
+                f, "card {} belongs to class {}", card, class
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DeckViolation {}
+
+impl DeckRules {
+    pub fn new(min_size: usize, max_size: usize, max_copies: usize) -> Self {
+        DeckRules {
+            min_size,
+            max_size,
+            max_copies,
+            banned: Vec::new(),
+            class_banned: HashMap::new(),
+        }
+    }
+
+    // Every way `list` breaks the rules for a deck of `class`, in the
+    // order of the checks: size first, then each distinct card in the
+    // order it first appears.
+    pub fn validate(
+        &self,
+        list: &[CardID],
+        class: Option<&str>,
+        cards: &[Card]
+    ) -> Vec::<DeckViolation> {
+        let mut violations = Vec::new();
+        if list.len() < self.min_size {
+            violations.push(DeckViolation::TooFew {
+                size: list.len(),
+                min: self.min_size,
+            });
+        }
+        if list.len() > self.max_size {
+            violations.push(DeckViolation::TooMany {
+                size: list.len(),
+                max: self.max_size,
+            });
+        }
+
+        let class_banned = class
+            .and_then(|class| self.class_banned.get(class))
+            .map(|banned| banned.as_slice())
+            .unwrap_or(&[]);
+        let mut seen: Vec::<CardID> = Vec::new();
+        for &id in list {
+            if seen.contains(&id) {
+                continue;
+            }
+            seen.push(id);
+
+            let card = match cards.get(id as usize) {
+                Some(card) => card,
+                None => {
+                    violations.push(DeckViolation::UnknownCard(id));
+                    continue;
+                }
+            };
+            let count = list.iter().filter(|&&other| other == id).count();
+            if count > self.max_copies {
+                violations.push(DeckViolation::TooManyCopies {
+                    card: id,
+                    count,
+                    max: self.max_copies,
+                });
+            }
+            if self.banned.contains(&id) || class_banned.contains(&id) {
+                violations.push(DeckViolation::Banned(id));
+            }
+            if let Some(own) = &card.class {
+                if class != Some(own.as_str()) {
+                    violations.push(DeckViolation::WrongClass {
+                        card: id,
+                        class: own.clone(),
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+
+    // A fresh deck with every card of `list` in the draw pile, if the
+    // list breaks none of the rules.
+    pub fn build(
+        &self,
+        list: &[CardID],
+        class: Option<&str>,
+        cards: &[Card]
+    ) -> Result<Deck, Vec::<DeckViolation>> {
+        let violations = self.validate(list, class, cards);
+        if violations.is_empty() {
+            Ok(Deck::new(list))
+        } else {
+            Err(violations)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use crate::script::Script;
+use crate::{
+    CharacterIdx, Encounter, Event, FeatureIdx, TraitID, TraitValue,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Who {
+    Player,
+    Target,
+}
+
+impl Who {
+    fn pick(self, player: CharacterIdx, target: CharacterIdx) -> CharacterIdx {
+        match self {
+            Who::Player => player,
+            Who::Target => target,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Effect {
+    ModifyTrait {
+        who: Who,
+        id: TraitID,
+        by: TraitValue,
+    },
+    SetTrait {
+        who: Who,
+        id: TraitID,
+        value: TraitValue,
+    },
+    Draw {
+        who: Who,
+        count: usize,
+    },
+    // Discards cards chosen at random from the hand.
+    Discard {
+        who: Who,
+        count: usize,
+    },
+    Script(Arc::<Script>),
+    // Cancels the stack entry this one responded to; see
+    // `Encounter::counter`.
+    Counter,
+}
+
+impl Effect {
+    pub fn apply(
+        &self,
+        encounter: &mut Encounter,
+        player: CharacterIdx,
+        target: CharacterIdx
+    ) {
+        self.resolve(encounter, player, target, None);
+    }
+
+    pub(crate) fn resolve(
+        &self,
+        encounter: &mut Encounter,
+        player: CharacterIdx,
+        target: CharacterIdx,
+        feature: Option<FeatureIdx>
+    ) {
+        match *self {
+            Effect::ModifyTrait { who, id, by } => {
+                encounter.modify_trait(who.pick(player, target), id, by);
+            }
+            Effect::SetTrait { who, id, value } => {
+                encounter.set_trait(who.pick(player, target), id, value);
+            }
+            Effect::Draw { who, count } => {
+                let cid = who.pick(player, target);
+                for _ in 0..count {
+                    encounter.draw_card(cid);
+                }
+            }
+            Effect::Discard { who, count } => {
+                let cid = who.pick(player, target);
+                for _ in 0..count {
+                    let size = match encounter.characters.get(cid) {
+                        Some(ch) => ch.deck.hand.len(),
+                        None => break,
+                    };
+                    if size == 0 {
+                        break;
+                    }
+                    let i = encounter.rng.below(size as u64) as usize;
+                    encounter.discard_card(cid, i);
+                }
+            }
+            Effect::Script(ref script) => {
+                let result = script.run(encounter, player, target, feature);
+                if let Err(error) = result {
+                    encounter.emit(Event::ScriptFailed {
+                        message: error.to_string(),
+                    });
+                }
+            }
+            Effect::Counter => {
+                encounter.counter();
+            }
+        }
+    }
+}
+
+// Card effect for cards whose behavior is entirely described by
+// `Card::effects`.
+pub fn no_effect(_: &mut Encounter, _: CharacterIdx, _: CharacterIdx) {}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::{
+    CardID, CharacterIdx, FeatureID, FeatureIdx, Phase, Targets, TraitID,
+    TraitValue,
+};
+
+// Why an action on an `Encounter` was rejected. Nothing has changed
+// when one of these is returned.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionError {
+    EncounterOver,
+    WrongPhase(Phase),
+    NotYourTurn {
+        character: CharacterIdx,
+        active: CharacterIdx,
+    },
+    NoSuchCharacter(CharacterIdx),
+    NoSuchHandCard(usize),
+    EmptySlot(CardID),
+    UnknownCard(CardID),
+    NoSuchFeature(FeatureIdx),
+    UnknownFeature(FeatureID),
+    FeatureDestroyed(FeatureIdx),
+    IllegalTarget {
+        target: usize,
+        targets: Targets,
+    },
+    CannotAfford {
+        id: TraitID,
+        cost: TraitValue,
+        available: TraitValue,
+    },
+    WindowOpen,
+    NoWindow,
+    NoPriority {
+        character: CharacterIdx,
+        priority: CharacterIdx,
+    },
+    NotReaction,
+    // The reaction feature belongs to another side.
+    NotYourReaction(FeatureIdx),
+    // The reaction feature already waits on the stack.
+    ReactionPending(FeatureIdx),
+    Conceded(CharacterIdx),
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ActionError::EncounterOver => write!(f, "the encounter is over"),
+            ActionError::WrongPhase(phase) => write!(
+                f, "not allowed during the {:?} phase", phase
+            ),
+            ActionError::NotYourTurn { character, active } => write!(
+                f, "character {} acted during the turn of {}",
+                character, active
+            ),
+            ActionError::NoSuchCharacter(cid) => write!(
+                f, "no character {}", cid
+            ),
+            ActionError::NoSuchHandCard(i) => write!(
+                f, "no card {} in hand", i
+            ),
+            ActionError::EmptySlot(card) => write!(
+                f, "deck slot {} holds no card", card
+            ),
+            ActionError::UnknownCard(card) => write!(
+                f, "unknown card {}", card
+            ),
+            ActionError::NoSuchFeature(fid) => write!(
+                f, "no feature {}", fid
+            ),
+            ActionError::UnknownFeature(id) => write!(
+                f, "unknown feature {}", id
+            ),
+            ActionError::FeatureDestroyed(fid) => write!(
+                f, "feature {} is destroyed", fid
+            ),
+            ActionError::IllegalTarget { target, targets } => write!(
+                f, "{} is not a legal target for a card targeting {:?}",
+                target, targets
+            ),
+            ActionError::CannotAfford { id, cost, available } => write!(
+                f, "costs {} of trait {} but only {} is available",
+                cost, id, available
+            ),
+            ActionError::WindowOpen => write!(
+                f, "waiting for responses to the stack"
+            ),
+            ActionError::NoWindow => write!(f, "nothing to respond to"),
+            ActionError::NoPriority { character, priority } => write!(
+                f, "character {} responded before {}", character, priority
+            ),
+            ActionError::NotReaction => write!(f, "not a reaction"),
+            ActionError::NotYourReaction(fid) => write!(
+                f, "feature {} reacts only for another side", fid
+            ),
+            ActionError::ReactionPending(fid) => write!(
+                f, "feature {} is already on the stack", fid
+            ),
+            ActionError::Conceded(cid) => write!(
+                f, "character {} has conceded", cid
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
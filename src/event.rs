@@ -0,0 +1,89 @@
+use crate::outcome::Outcome;
+use crate::status::StatusID;
+use crate::{
+    CardID, CharacterIdx, FeatureID, FeatureIdx, TraitID, TraitValue,
+};
+
+// Card ids in events are the entries held in `Deck::hand`, `deck` and
+// `discard`; `CardPlayed::kind` is the resolved index into `card_list`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    TurnStarted {
+        character: CharacterIdx,
+        round: u64,
+    },
+    TurnEnded {
+        character: CharacterIdx,
+    },
+    CardPlayed {
+        character: CharacterIdx,
+        target: CharacterIdx,
+        card: CardID,
+        kind: CardID,
+    },
+    CardDrawn {
+        character: CharacterIdx,
+        card: CardID,
+    },
+    Discarded {
+        character: CharacterIdx,
+        card: CardID,
+    },
+    Reshuffled {
+        character: CharacterIdx,
+    },
+    TraitChanged {
+        character: CharacterIdx,
+        id: TraitID,
+        old: Option<TraitValue>,
+        new: TraitValue,
+    },
+    StatusApplied {
+        character: CharacterIdx,
+        id: StatusID,
+        stacks: u32,
+        turns: Option<u32>,
+    },
+    StatusRemoved {
+        character: CharacterIdx,
+        id: StatusID,
+    },
+    FeatureActivated {
+        feature: FeatureIdx,
+        id: FeatureID,
+    },
+    FeatureTriggered {
+        feature: FeatureIdx,
+        id: FeatureID,
+    },
+    CardTriggered {
+        character: CharacterIdx,
+        card: CardID,
+        kind: CardID,
+    },
+    // Triggers stopped firing for the rest of an action.
+    TriggerLimitReached,
+    // The top of the stack awaits responses from `waiting`, in order.
+    ResponseWindow {
+        waiting: Vec::<CharacterIdx>,
+    },
+    Passed {
+        character: CharacterIdx,
+    },
+    // A stack entry put there by `character` was removed unresolved.
+    Countered {
+        character: CharacterIdx,
+    },
+    ScriptFailed {
+        message: String,
+    },
+    FeatureDestroyed {
+        feature: FeatureIdx,
+    },
+    Conceded {
+        character: CharacterIdx,
+    },
+    EncounterEnded {
+        outcome: Option<Outcome>,
+    },
+}
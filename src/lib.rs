@@ -1,13 +1,79 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-type PlayerID = u64;
-type CardID = u64;
-type TraitID = u64;
-type FeatureID = u64;
-type TraitValue = i64;
-type CharacterIdx = usize;
-type FeatureIdx = usize;
+#[macro_use]
+extern crate serde_derive;
+
+pub mod ai;
+pub mod campaign;
+pub mod deckbuild;
+pub mod effect;
+pub mod error;
+pub mod event;
+pub mod loader;
+pub mod outcome;
+pub mod protocol;
+pub mod replay;
+pub mod rng;
+pub mod script;
+pub mod sim;
+pub mod snapshot;
+pub mod stack;
+pub mod status;
+pub mod trigger;
+pub mod undo;
+pub mod view;
+
+pub use ai::Controller;
+pub use campaign::Run;
+pub use deckbuild::{DeckRules, DeckViolation};
+pub use effect::Effect;
+pub use error::ActionError;
+pub use event::Event;
+pub use outcome::{EndCondition, EndReason, Outcome};
+pub use protocol::{Request, Response};
+pub use replay::{Command, Recording};
+pub use rng::Rng;
+pub use script::Script;
+pub use snapshot::Snapshot;
+pub use stack::{StackEntry, Window};
+pub use status::{Status, StatusEffect};
+pub use trigger::Trigger;
+pub use undo::UndoMode;
+pub use view::GameView;
+
+pub type PlayerID = u64;
+pub type CardID = u64;
+pub type TraitID = u64;
+pub type FeatureID = u64;
+pub type TraitValue = i64;
+pub type CharacterIdx = usize;
+pub type FeatureIdx = usize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Phase {
+    Setup,
+    Start,
+    Main,
+    End,
+}
+
+// What a card may be played on. `Own` is the player itself, `Ally` is
+// another character on the same side, and `AllEnemies` resolves the card
+// once against every character on another side. A `Feature` card's
+// `effect` is passed the feature index as its target, while its
+// `effects` get it as the script's `feature` and treat the player as
+// the target.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Targets {
+    None,
+    Own,
+    Ally,
+    Enemy,
+    Any,
+    AllEnemies,
+    Feature,
+}
 
 pub struct Card {
     pub name: String,
@@ -15,18 +81,129 @@ pub struct Card {
     pub effect: fn(
         &mut Encounter, player: CharacterIdx, target: CharacterIdx
     ),
+    pub targets: Targets,
+    // Paid from the player's base trait values before the card resolves.
+    pub cost: Vec::<(TraitID, TraitValue)>,
+    // Called at each phase change while the card is in the active
+    // character's hand.
+    pub on_phase: Option<fn(
+        &mut Encounter, player: CharacterIdx, phase: Phase
+    )>,
+    // Applied in order after `effect`.
+    pub effects: Vec::<Effect>,
+    // Only decks built for this class may include the card; `None`
+    // marks a neutral card any deck can use.
+    pub class: Option<String>,
+    // While the card is in a hand, each of these runs `effect` and
+    // `effects` with the holder as player; see `trigger::Trigger`.
+    pub triggers: Vec::<Trigger>,
+    // Reactions can be played in response windows on other characters'
+    // turns, and stay in hand when their holder's turn ends.
+    pub reaction: bool,
+}
+
+impl Card {
+    pub fn new(
+        name: &str,
+        description: &str,
+        effect: fn(&mut Encounter, CharacterIdx, CharacterIdx)
+    ) -> Self {
+        Card {
+            name: name.to_string(),
+            description: description.to_string(),
+            effect,
+            targets: Targets::Any,
+            cost: Vec::new(),
+            on_phase: None,
+            effects: Vec::new(),
+            class: None,
+            triggers: Vec::new(),
+            reaction: false,
+        }
+    }
 }
 
+// How a resource trait is restored at the start of each of a
+// character's turns: set to a value, raised by an amount, or set to the
+// value of another trait such as a maximum.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Refill {
+    Set(TraitValue),
+    Add(TraitValue),
+    ToTrait(TraitID),
+}
+
+// Values of a trait are clamped to `min..=max`. A character without a
+// stored value has `default`, and a trait with `derive` is never stored
+// but computed from the character's other traits.
 pub struct Trait {
     pub name: String,
+    pub min: TraitValue,
+    pub max: TraitValue,
+    pub default: TraitValue,
+    pub derive: Option<fn(&Encounter, CharacterIdx) -> TraitValue>,
+    pub refill: Option<Refill>,
+}
+
+impl Trait {
+    pub fn new(name: &str) -> Self {
+        Trait {
+            name: name.to_string(),
+            min: TraitValue::MIN,
+            max: TraitValue::MAX,
+            default: 0,
+            derive: None,
+            refill: None,
+        }
+    }
+
+    pub fn clamp(&self, value: TraitValue) -> TraitValue {
+        value.max(self.min).min(self.max)
+    }
 }
 
 pub struct Feature {
     pub name: String,
     pub description: String,
     pub effect: fn(&mut Encounter, feature: FeatureIdx),
+    // Called at each phase change of every turn.
+    pub on_phase: Option<fn(
+        &mut Encounter, feature: FeatureIdx, phase: Phase
+    )>,
+    // Applied in order after `effect`, with the active character as
+    // both player and target.
+    pub effects: Vec::<Effect>,
+    // Events that run the feature as if activated, but with the
+    // character the event is about as target; see `trigger::Trigger`.
+    pub triggers: Vec::<Trigger>,
+    // Reaction features can also be activated in response windows; see
+    // `Encounter::respond_feature`.
+    pub reaction: bool,
+    // The side whose characters may use the reaction, or `None` for
+    // every side.
+    pub side: Option<usize>,
+}
+
+impl Feature {
+    pub fn new(
+        name: &str,
+        description: &str,
+        effect: fn(&mut Encounter, FeatureIdx)
+    ) -> Self {
+        Feature {
+            name: name.to_string(),
+            description: description.to_string(),
+            effect,
+            on_phase: None,
+            effects: Vec::new(),
+            triggers: Vec::new(),
+            reaction: false,
+            side: None,
+        }
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Deck {
     pub clist: Vec::<Option<CardID>>,
     pub deck: Vec::<CardID>,
@@ -35,17 +212,32 @@ pub struct Deck {
 }
 
 impl Deck {
-    pub fn draw_card(&mut self) {
-        if self.deck.len() == 0 {
+    // A deck holding one slot for each card in `cards`, all of them in
+    // the draw pile.
+    pub fn new(cards: &[CardID]) -> Self {
+        Deck {
+            clist: cards.iter().map(|&id| Some(id)).collect(),
+            deck: (0..cards.len() as CardID).collect(),
+            hand: Vec::new(),
+            discard: Vec::new(),
+        }
+    }
+
+    pub fn shuffle(&mut self, rng: &mut Rng) {
+        rng.shuffle(&mut self.deck);
+    }
+
+    pub fn draw_card(&mut self, rng: &mut Rng) -> Option<CardID> {
+        if self.deck.is_empty() {
             std::mem::swap(
                 &mut self.deck,
                 &mut self.discard
             );
+            self.shuffle(rng);
         }
-        if let Some(&cid) = self.deck.last() {
-            self.hand.push(cid);
-            self.deck.pop();
-        }
+        let cid = self.deck.pop()?;
+        self.hand.push(cid);
+        Some(cid)
     }
 
     pub fn discard_card(&mut self, index: usize) {
@@ -58,71 +250,661 @@ impl Deck {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Character {
-    pub traits: HashMap::<TraitID,TraitValue>,
+    traits: HashMap::<TraitID,TraitValue>,
     pub deck: Deck,
+    #[serde(default)]
+    pub side: usize,
+    #[serde(default)]
+    statuses: Vec::<StatusEffect>,
+}
+
+impl Character {
+    pub fn new(traits: HashMap::<TraitID,TraitValue>, deck: Deck) -> Self {
+        Character {
+            traits,
+            deck,
+            side: 0,
+            statuses: Vec::new(),
+        }
+    }
+
+    // The stored trait values. Use `Encounter::get_trait` for the value
+    // with defaults and derived traits applied.
+    pub fn traits(&self) -> &HashMap::<TraitID,TraitValue> {
+        &self.traits
+    }
 }
 
+// The shared definitions games are played with. Cards, features, traits
+// and statuses are referenced by their index in these lists.
+#[derive(Clone)]
+pub struct Rules {
+    pub card_list: Arc::<Vec::<Card>>,
+    pub feature_list: Arc::<Vec::<Feature>>,
+    pub trait_list: Arc::<Vec::<Trait>>,
+    pub status_list: Arc::<Vec::<Status>>,
+    pub end_conditions: Vec::<EndCondition>,
+}
+
+impl Rules {
+    pub fn new(
+        card_list: Arc::<Vec::<Card>>,
+        feature_list: Arc::<Vec::<Feature>>
+    ) -> Self {
+        Rules {
+            card_list,
+            feature_list,
+            trait_list: Arc::new(Vec::new()),
+            status_list: Arc::new(Vec::new()),
+            end_conditions: Vec::new(),
+        }
+    }
+}
+
+// Defaults for `Encounter::script_step_limit` and `trigger_limit`.
+pub(crate) const SCRIPT_STEP_LIMIT: u64 = 10_000;
+pub(crate) const TRIGGER_LIMIT: usize = 100;
+
+#[derive(Clone)]
 pub struct Encounter {
     pub characters: Vec<Character>,
     pub features: Vec<FeatureID>,
     pub done: bool,
     pub card_list: Arc::<Vec::<Card>>,
-    pub feature_list: Arc::<Vec::<Feature>>
+    pub feature_list: Arc::<Vec::<Feature>>,
+    pub trait_list: Arc::<Vec::<Trait>>,
+    pub status_list: Arc::<Vec::<Status>>,
+    pub end_conditions: Vec::<EndCondition>,
+    pub outcome: Option<Outcome>,
+    pub active: CharacterIdx,
+    pub phase: Phase,
+    pub round: u64,
+    pub draw_count: usize,
+    pub rng: Rng,
+    pub script_step_limit: u64,
+    // Triggered effects allowed to fire in response to one action.
+    pub trigger_limit: usize,
+    destroyed: Vec::<FeatureIdx>,
+    // Characters taken out by `concede`, who lose their turns.
+    conceded: Vec::<CharacterIdx>,
+    events: Vec<Event>,
+    // Events before this index have been dispatched to triggers.
+    dispatched: usize,
+    stack: Vec::<StackEntry>,
+    window: Option<Window>,
 }
 
 impl Encounter {
+    pub fn new(
+        characters: Vec::<Character>,
+        features: Vec::<FeatureID>,
+        rules: &Rules,
+        seed: u64
+    ) -> Self {
+        let mut characters = characters;
+        for character in characters.iter_mut() {
+            for (&id, value) in character.traits.iter_mut() {
+                if let Some(def) = rules.trait_list.get(id as usize) {
+                    *value = def.clamp(*value);
+                }
+            }
+        }
+        Encounter {
+            characters,
+            features,
+            done: false,
+            card_list: Arc::clone(&rules.card_list),
+            feature_list: Arc::clone(&rules.feature_list),
+            trait_list: Arc::clone(&rules.trait_list),
+            status_list: Arc::clone(&rules.status_list),
+            end_conditions: rules.end_conditions.clone(),
+            outcome: None,
+            active: 0,
+            phase: Phase::Setup,
+            round: 0,
+            draw_count: 5,
+            rng: Rng::new(seed),
+            script_step_limit: SCRIPT_STEP_LIMIT,
+            trigger_limit: TRIGGER_LIMIT,
+            destroyed: Vec::new(),
+            conceded: Vec::new(),
+            events: Vec::new(),
+            dispatched: 0,
+            stack: Vec::new(),
+            window: None,
+        }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    // Ends the encounter without a winner.
+    pub fn end(&mut self) {
+        self.finish(None);
+    }
+
+    fn finish(&mut self, outcome: Option<Outcome>) {
+        if !self.done {
+            self.done = true;
+            self.outcome = outcome.clone();
+            self.emit(Event::EncounterEnded { outcome });
+        }
+    }
+
+    // The trait value before status modifiers: derived, stored or the
+    // trait's default, clamped to its bounds.
+    pub fn base_trait(&self, cid: CharacterIdx, id: TraitID) -> TraitValue {
+        let def = self.trait_list.get(id as usize);
+        let value = match def.and_then(|t| t.derive) {
+            Some(derive) => derive(self, cid),
+            None => self.characters.get(cid)
+                .and_then(|ch| ch.traits.get(&id).copied())
+                .or(def.map(|t| t.default))
+                .unwrap_or(0),
+        };
+        def.map_or(value, |t| t.clamp(value))
+    }
+
+    pub fn get_trait(&self, cid: CharacterIdx, id: TraitID) -> TraitValue {
+        let value = self.base_trait(cid, id)
+            .saturating_add(self.status_modifier(cid, id));
+        self.trait_list.get(id as usize).map_or(value, |t| t.clamp(value))
+    }
+
+    // Effective values of every trait the character could have, used to
+    // report what a mutation changed.
+    fn watch_traits(&self, cid: CharacterIdx) -> Vec::<(TraitID, TraitValue)> {
+        let mut ids: Vec::<TraitID> = (0..self.trait_list.len() as TraitID)
+            .collect();
+        if let Some(character) = self.characters.get(cid) {
+            ids.extend(character.traits.keys());
+            for status in character.statuses.iter() {
+                if let Some(def) = self.status_list.get(status.id as usize) {
+                    ids.extend(def.modifiers.iter().map(|&(id, _)| id));
+                }
+            }
+        }
+        ids.sort();
+        ids.dedup();
+        ids.into_iter().map(|id| (id, self.get_trait(cid, id))).collect()
+    }
+
+    fn notify_traits(
+        &mut self,
+        cid: CharacterIdx,
+        before: Vec::<(TraitID, TraitValue)>
+    ) {
+        let before: HashMap::<_, _> = before.into_iter().collect();
+        for (id, new) in self.watch_traits(cid) {
+            let old = before.get(&id).copied()
+                .unwrap_or_else(|| {
+                    self.trait_list.get(id as usize).map_or(0, |t| t.default)
+                });
+            if old != new {
+                self.emit(Event::TraitChanged {
+                    character: cid,
+                    id,
+                    old: Some(old),
+                    new,
+                });
+            }
+        }
+    }
+
+    // Stores a clamped base value and reports every effective trait that
+    // changed as a result. Fails for derived traits and unknown
+    // characters.
+    pub fn set_trait(
+        &mut self,
+        cid: CharacterIdx,
+        id: TraitID,
+        value: TraitValue
+    ) -> bool {
+        let def = self.trait_list.get(id as usize);
+        if cid >= self.characters.len()
+            || def.is_some_and(|t| t.derive.is_some())
+        {
+            return false;
+        }
+
+        let value = def.map_or(value, |t| t.clamp(value));
+        let before = self.watch_traits(cid);
+        self.characters[cid].traits.insert(id, value);
+        self.notify_traits(cid, before);
+        true
+    }
+
+    pub fn modify_trait(
+        &mut self,
+        cid: CharacterIdx,
+        id: TraitID,
+        delta: TraitValue
+    ) -> bool {
+        let old = self.base_trait(cid, id);
+        self.set_trait(cid, id, old.saturating_add(delta))
+    }
+
+    pub fn draw_card(&mut self, cid: CharacterIdx) -> Option<CardID> {
+        let deck = &mut self.characters.get_mut(cid)?.deck;
+        let reshuffle = deck.deck.is_empty() && !deck.discard.is_empty();
+        let card = deck.draw_card(&mut self.rng);
+        if reshuffle {
+            self.emit(Event::Reshuffled { character: cid });
+        }
+        if let Some(card) = card {
+            self.emit(Event::CardDrawn { character: cid, card });
+        }
+        card
+    }
+
+    pub fn discard_card(&mut self, cid: CharacterIdx, i: usize) -> bool {
+        if let Some(character) = self.characters.get_mut(cid) {
+            if let Some(&card) = character.deck.hand.get(i) {
+                character.deck.discard_card(i);
+                self.emit(Event::Discarded { character: cid, card });
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn discard_hand(&mut self, cid: CharacterIdx) {
+        while self.characters.get(cid)
+            .is_some_and(|ch| !ch.deck.hand.is_empty())
+        {
+            self.discard_card(cid, 0);
+        }
+    }
+
+    pub fn start(&mut self) -> bool {
+        if self.phase != Phase::Setup || self.characters.is_empty() {
+            return false;
+        }
+
+        for character in self.characters.iter_mut() {
+            character.deck.shuffle(&mut self.rng);
+        }
+        self.round = 1;
+        self.begin_turn(0);
+        self.dispatch();
+        true
+    }
+
+    pub fn end_turn(&mut self) -> Result<(), ActionError> {
+        if self.done {
+            return Err(ActionError::EncounterOver);
+        }
+        if self.phase != Phase::Main {
+            return Err(ActionError::WrongPhase(self.phase));
+        }
+        if self.window.is_some() {
+            return Err(ActionError::WindowOpen);
+        }
+
+        self.finish_turn();
+        self.dispatch();
+        self.check_end();
+        Ok(())
+    }
+
+    fn finish_turn(&mut self) {
+        let cid = self.active;
+        self.set_phase(Phase::End);
+        let mut i = 0;
+        while i < self.characters[cid].deck.hand.len() {
+            if self.hand_card(cid, i).is_some_and(|cd| cd.reaction) {
+                i += 1;
+            } else {
+                self.discard_card(cid, i);
+            }
+        }
+        self.expire_statuses(cid);
+        self.emit(Event::TurnEnded { character: cid });
+
+        let next = (cid + 1) % self.characters.len();
+        if next == 0 {
+            self.round += 1;
+        }
+        self.begin_turn(next);
+    }
+
+    // Stunned characters skip straight to the end of their turn, and
+    // characters who conceded get no turn at all. If everyone is
+    // stunned, a full round passes before play resumes.
+    fn begin_turn(&mut self, cid: CharacterIdx) {
+        let mut cid = cid;
+        for skipped in 0..=self.characters.len() {
+            if self.has_conceded(cid) && skipped < self.characters.len() {
+                cid = (cid + 1) % self.characters.len();
+                if cid == 0 {
+                    self.round += 1;
+                }
+                continue;
+            }
+            self.active = cid;
+            self.emit(Event::TurnStarted {
+                character: cid,
+                round: self.round,
+            });
+            self.set_phase(Phase::Start);
+            self.tick_statuses(cid);
+            if self.is_stunned(cid) && skipped < self.characters.len() {
+                self.set_phase(Phase::End);
+                self.expire_statuses(cid);
+                self.emit(Event::TurnEnded { character: cid });
+                cid = (cid + 1) % self.characters.len();
+                if cid == 0 {
+                    self.round += 1;
+                }
+                continue;
+            }
+
+            self.refill_traits(cid);
+            for _ in 0..self.draw_count {
+                self.draw_card(cid);
+            }
+            self.set_phase(Phase::Main);
+            return;
+        }
+    }
+
+    fn refill_traits(&mut self, cid: CharacterIdx) {
+        let trait_list = Arc::clone(&self.trait_list);
+        for (id, def) in trait_list.iter().enumerate() {
+            let id = id as TraitID;
+            match def.refill {
+                Some(Refill::Set(value)) => {
+                    self.set_trait(cid, id, value);
+                }
+                Some(Refill::Add(amount)) => {
+                    self.modify_trait(cid, id, amount);
+                }
+                Some(Refill::ToTrait(other)) => {
+                    let value = self.get_trait(cid, other);
+                    self.set_trait(cid, id, value);
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Costs summed per trait. They are paid out of base values, so they
+    // are checked against those too: status modifiers don't help, and a
+    // cost is only affordable if paying it keeps the trait at or above
+    // its minimum. Derived traits can't be paid from.
+    fn check_cost(
+        &self,
+        pid: CharacterIdx,
+        cost: &[(TraitID, TraitValue)]
+    ) -> Result<(), ActionError> {
+        let mut total: Vec::<(TraitID, TraitValue)> = Vec::new();
+        for &(id, amount) in cost {
+            match total.iter_mut().find(|(t, _)| *t == id) {
+                Some((_, sum)) => *sum = sum.saturating_add(amount),
+                None => total.push((id, amount)),
+            }
+        }
+        for (id, cost) in total {
+            let def = self.trait_list.get(id as usize);
+            let available = self.base_trait(pid, id);
+            let min = def.map_or(TraitValue::MIN, |t| t.min);
+            if def.is_some_and(|t| t.derive.is_some())
+                || available < cost
+                || available.saturating_sub(cost) < min
+            {
+                return Err(ActionError::CannotAfford {
+                    id,
+                    cost,
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn set_phase(&mut self, phase: Phase) {
+        self.phase = phase;
+
+        let feature_list = Arc::clone(&self.feature_list);
+        let mut fid = 0;
+        while fid < self.features.len() {
+            let n = self.features[fid];
+            if let Some(feature) = feature_list.get(n as usize) {
+                if let Some(on_phase) = feature.on_phase {
+                    on_phase(self, fid, phase);
+                }
+            }
+            fid += 1;
+        }
+
+        let card_list = Arc::clone(&self.card_list);
+        let pid = self.active;
+        let hand = self.characters[pid].deck.hand.clone();
+        for cid in hand {
+            let deck = &self.characters[pid].deck;
+            if let Some(Some(n)) = deck.clist.get(cid as usize) {
+                if let Some(card) = card_list.get((*n) as usize) {
+                    if let Some(on_phase) = card.on_phase {
+                        on_phase(self, pid, phase);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn play_card(
         &mut self,
         pid: CharacterIdx,
         tid: CharacterIdx,
         i: usize
-    ) -> bool {
-        let max = self.characters.len();
-        if pid < max && tid < max {
-            let deck = &mut self.characters[pid].deck;
+    ) -> Result<(), ActionError> {
+        if self.done {
+            return Err(ActionError::EncounterOver);
+        }
+        if self.phase != Phase::Main {
+            return Err(ActionError::WrongPhase(self.phase));
+        }
+        if self.window.is_some() {
+            return Err(ActionError::WindowOpen);
+        }
+        let (slot, kind, targets, feature) =
+            self.prepare_card(pid, tid, i, false)?;
+        self.pay_card(pid, i, slot, kind, &targets, feature);
+        self.push_entry(pid, StackEntry::Card {
+            character: pid,
+            targets,
+            feature,
+            card: slot,
+            kind,
+        });
+        self.dispatch();
+        self.check_end();
+
+        Ok(())
+    }
+
+    fn hand_card(&self, cid: CharacterIdx, i: usize) -> Option<&Card> {
+        let deck = &self.characters.get(cid)?.deck;
+        let &slot = deck.hand.get(i)?;
+        let n = deck.clist.get(slot as usize).copied().flatten()?;
+        self.card_list.get(n as usize)
+    }
+
+    // Checks that hand card `i` can be played now, and returns its slot,
+    // its kind, the characters it will resolve on and the feature it
+    // targets, if any. A card targeting a feature resolves once with the
+    // player standing in as its character target. Reactions are played
+    // outside the player's turn.
+    fn prepare_card(
+        &self,
+        pid: CharacterIdx,
+        tid: CharacterIdx,
+        i: usize,
+        reaction: bool
+    ) -> Result<
+        (CardID, CardID, Vec::<CharacterIdx>, Option<FeatureIdx>),
+        ActionError
+    > {
+        let deck = &self.characters.get(pid)
+            .ok_or(ActionError::NoSuchCharacter(pid))?
+            .deck;
+        if !reaction && pid != self.active {
+            return Err(ActionError::NotYourTurn {
+                character: pid,
+                active: self.active,
+            });
+        }
+        let &cid = deck.hand.get(i)
+            .ok_or(ActionError::NoSuchHandCard(i))?;
+        let n = deck.clist.get(cid as usize)
+            .copied()
+            .flatten()
+            .ok_or(ActionError::EmptySlot(cid))?;
+        let cd = self.card_list.get(n as usize)
+            .ok_or(ActionError::UnknownCard(n))?;
+        if reaction && !cd.reaction {
+            return Err(ActionError::NotReaction);
+        }
+        if !self.can_target(pid, tid, cd.targets) {
+            return Err(ActionError::IllegalTarget {
+                target: tid,
+                targets: cd.targets,
+            });
+        }
+        self.check_cost(pid, &cd.cost)?;
+        let (targets, feature) = match cd.targets {
+            Targets::None => (vec![pid], None),
+            Targets::AllEnemies => {
+                (self.legal_targets_for(pid, cd.targets), None)
+            }
+            Targets::Feature => (vec![pid], Some(tid)),
+            _ => (vec![tid], None),
+        };
+
+        Ok((cid, n, targets, feature))
+    }
+
+    // Pays for a card checked by `prepare_card` and moves it to the
+    // discard pile.
+    fn pay_card(
+        &mut self,
+        pid: CharacterIdx,
+        i: usize,
+        slot: CardID,
+        kind: CardID,
+        targets: &[CharacterIdx],
+        feature: Option<FeatureIdx>
+    ) {
+        let card_list = Arc::clone(&self.card_list);
+        for &(id, amount) in card_list[kind as usize].cost.iter() {
+            self.modify_trait(pid, id, -amount);
+        }
+        self.characters[pid].deck.discard_card(i);
+        self.emit(Event::CardPlayed {
+            character: pid,
+            target: feature.or(targets.first().copied()).unwrap_or(pid),
+            card: slot,
+            kind,
+        });
+    }
+    // The targets a hand card can be played on: character indices, or
+    // feature indices for `Targets::Feature`. For `Targets::AllEnemies`
+    // these are the characters the card will hit, and for
+    // `Targets::None` the list is empty.
+    pub fn legal_targets(&self, pid: CharacterIdx, i: usize) -> Vec<usize> {
+        if let Some(character) = self.characters.get(pid) {
+            let deck = &character.deck;
             if let Some(&cid) = deck.hand.get(i) {
-                if let Some(Some(n))
-                    = deck.clist.get(cid as usize)
-                {
-                    if let Some(cd) = self.card_list
-                        .get((*n) as usize)
-                    {
-                        deck.discard_card(i);
-                        (cd.effect)(
-                            self,
-                            pid,
-                            tid
-                        );
-                        return true;
+                if let Some(&Some(n)) = deck.clist.get(cid as usize) {
+                    if let Some(cd) = self.card_list.get(n as usize) {
+                        return self.legal_targets_for(pid, cd.targets);
                     }
                 }
             }
         }
 
-        false
+        Vec::new()
+    }
+
+    fn legal_targets_for(
+        &self,
+        pid: CharacterIdx,
+        targets: Targets
+    ) -> Vec<usize> {
+        let (max, targets) = match targets {
+            Targets::None => (0, targets),
+            Targets::Feature => (self.features.len(), targets),
+            Targets::AllEnemies => (self.characters.len(), Targets::Enemy),
+            _ => (self.characters.len(), targets),
+        };
+        (0..max).filter(|&tid| self.can_target(pid, tid, targets)).collect()
+    }
+
+    fn can_target(
+        &self,
+        pid: CharacterIdx,
+        tid: usize,
+        targets: Targets
+    ) -> bool {
+        let side = |cid: CharacterIdx| self.characters[cid].side;
+        let max = self.characters.len();
+        match targets {
+            Targets::None | Targets::AllEnemies => true,
+            Targets::Own => tid == pid,
+            Targets::Ally => tid < max && tid != pid && side(tid) == side(pid),
+            Targets::Enemy => tid < max && side(tid) != side(pid),
+            Targets::Any => tid < max,
+            Targets::Feature => tid < self.features.len(),
+        }
     }
 
     pub fn activate_feature(
         &mut self,
         fid: usize,
-    ) -> bool {
-        if let Some(n) = self.features.get(fid) {
-            if let Some(feature) = self.feature_list
-                .get((*n) as usize)
-            {
-                (feature.effect)(
-                    self,
-                    fid
-                );
-                return true;
-            }
+    ) -> Result<(), ActionError> {
+        if self.done {
+            return Err(ActionError::EncounterOver);
+        }
+        if self.window.is_some() {
+            return Err(ActionError::WindowOpen);
+        }
+        let &n = self.features.get(fid)
+            .ok_or(ActionError::NoSuchFeature(fid))?;
+        let feature_list = Arc::clone(&self.feature_list);
+        let feature = feature_list.get(n as usize)
+            .ok_or(ActionError::UnknownFeature(n))?;
+        if self.is_destroyed(fid) {
+            return Err(ActionError::FeatureDestroyed(fid));
         }
 
-        false
+        self.emit(Event::FeatureActivated {
+            feature: fid,
+            id: n,
+        });
+        (feature.effect)(
+            self,
+            fid
+        );
+        let active = self.active;
+        for effect in feature.effects.iter() {
+            effect.resolve(self, active, active, Some(fid));
+        }
+        self.dispatch();
+        self.check_end();
+
+        Ok(())
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: PlayerID,
     pub character: Character,
@@ -131,15 +913,25 @@ pub struct Player {
 pub struct Game {
     pub players: HashMap::<PlayerID, CharacterIdx>,
     pub encounter: Encounter,
+    pub recording: Recording,
+    pub history: undo::History,
+    // Characters without a player can be given a controller to act for
+    // them; see `Game::run_controllers`.
+    pub controllers: HashMap::<CharacterIdx, Box::<dyn Controller>>,
 }
 
 impl Game {
     pub fn new(
         players: Vec::<Player>,
         features: Vec::<FeatureID>,
-        card_list: Arc::<Vec::<Card>>,
-        feature_list: Arc::<Vec::<Feature>>
+        rules: &Rules,
+        seed: u64
     ) -> Self {
+        let setup = replay::Setup {
+            players: players.clone(),
+            features: features.clone(),
+            seed,
+        };
         let mut player_map = HashMap::new();
         let mut characters = Vec::with_capacity(players.len());
         for player in players {
@@ -148,24 +940,49 @@ impl Game {
             );
             characters.push(player.character);
         }
+        let mut encounter = Encounter::new(
+            characters,
+            features,
+            rules,
+            seed
+        );
+        encounter.start();
         Game {
             players: player_map,
-            encounter: Encounter {
-                characters: characters,
-                features: features,
-                done: false,
-                card_list: card_list,
-                feature_list: feature_list,
+            encounter,
+            recording: Recording {
+                setup,
+                steps: Vec::new(),
             },
+            history: undo::History::new(UndoMode::Disabled),
+            controllers: HashMap::new(),
         }
     }
 
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.encounter.outcome.as_ref()
+    }
+
+    // The players whose characters are on the winning side.
+    pub fn winners(&self) -> Vec::<PlayerID> {
+        let side = match self.outcome().and_then(|o| o.winner) {
+            Some(side) => side,
+            None => return Vec::new(),
+        };
+        let mut winners: Vec::<PlayerID> = self.players.iter()
+            .filter(|(_, &cid)| self.encounter.characters[cid].side == side)
+            .map(|(&id, _)| id)
+            .collect();
+        winners.sort();
+        winners
+    }
+
     pub fn get_character(
         &self, pid: PlayerID
     ) -> Option<&Character> {
         self.players.get(&pid).and_then(
             |&ch_id| -> Option<&Character> {
-                self.encounter.characters.get(ch_id as usize)
+                self.encounter.characters.get(ch_id)
             }
         )
     }
@@ -175,7 +992,7 @@ impl Game {
     ) -> Option<&mut Character> {
         self.players.get(&pid).and_then(
             |&ch_id| -> Option<&mut Character> {
-                self.encounter.characters.get_mut(ch_id as usize)
+                self.encounter.characters.get_mut(ch_id)
             }
         )
     }
@@ -0,0 +1,422 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::effect::{no_effect, Effect, Who};
+use crate::script::{Script, ScriptError};
+use crate::{
+    Card, CardID, EndCondition, Refill, Rules, Targets, Trait, TraitID,
+    TraitValue, Trigger,
+};
+
+// Card definition files are line based. Blank lines and lines starting
+// with `#` are ignored, and every other line is a key followed by its
+// arguments:
+//
+//     card Strike
+//     description Deal 6 damage.
+//     target enemy
+//     cost energy 1
+//     effect modify target health -6
+//     effect draw player 1
+//
+// `card` starts a new definition, under a name no other card has.
+// `target` is one of `none`, `self`, `ally`, `enemy`, `any`,
+// `all-enemies` or `feature`, and defaults to `any`. Each `cost TRAIT N`
+// line adds N, at least 1, of a trait to the price paid by the player.
+// Effects are `modify WHO TRAIT N`, `set WHO TRAIT N`, `draw WHO N` and
+// `discard WHO N`, where WHO is `player` or `target` and TRAIT is a
+// trait name or a numeric id. `class NAME` limits the card to decks
+// built for that class. `trigger` lines make the card's effects also
+// fire from the hand on `turn-start`, `turn-end`, `card-played`,
+// `defeated` or `below TRAIT N` (see `trigger::Trigger`).
+// A `reaction` line lets the card be played in response windows, where
+// `effect counter` cancels what it responds to.
+// Consecutive or scattered `script` lines are joined into one script
+// (see `script::Script`) that runs after the card's other effects.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LoadError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+pub fn load_cards(
+    path: &Path,
+    traits: &[Trait]
+) -> Result<Vec::<Card>, LoadError> {
+    let file = path.display().to_string();
+    match fs::read_to_string(path) {
+        Ok(src) => parse_cards(&src, &file, traits),
+        Err(e) => Err(LoadError {
+            file,
+            line: 0,
+            message: e.to_string(),
+        }),
+    }
+}
+
+pub fn parse_cards(
+    src: &str,
+    file: &str,
+    traits: &[Trait]
+) -> Result<Vec::<Card>, LoadError> {
+    let mut cards: Vec::<Card> = Vec::new();
+    let mut script = PendingScript::default();
+
+    for (n, text) in src.lines().enumerate() {
+        let err = |message: String| LoadError {
+            file: file.to_string(),
+            line: n + 1,
+            message,
+        };
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let (key, rest) = match text.split_once(char::is_whitespace) {
+            Some((key, rest)) => (key, rest.trim()),
+            None => (text, ""),
+        };
+
+        if key == "card" {
+            if rest.is_empty() {
+                return Err(err("card needs a name".into()));
+            }
+            // Deck lists refer to cards by name.
+            if cards.iter().any(|card| card.name == rest) {
+                return Err(err(format!("duplicate card '{}'", rest)));
+            }
+            script.finish(cards.last_mut(), file)?;
+            cards.push(Card::new(rest, "", no_effect));
+            continue;
+        }
+
+        let card = match cards.last_mut() {
+            Some(card) => card,
+            None => return Err(err(format!("'{}' before any card", key))),
+        };
+        match key {
+            "description" => {
+                if !card.description.is_empty() {
+                    card.description.push('\n');
+                }
+                card.description.push_str(rest);
+            }
+            "target" => {
+                card.targets = match rest {
+                    "none" => Targets::None,
+                    "self" => Targets::Own,
+                    "ally" => Targets::Ally,
+                    "enemy" => Targets::Enemy,
+                    "any" => Targets::Any,
+                    "all-enemies" => Targets::AllEnemies,
+                    "feature" => Targets::Feature,
+                    _ => return Err(err(format!("unknown target '{}'", rest))),
+                };
+            }
+            "cost" => {
+                let args: Vec::<&str> = rest.split_whitespace().collect();
+                if args.len() != 2 {
+                    return Err(err("cost takes a trait and an amount".into()));
+                }
+                let id = parse_trait(args[0], traits).map_err(err)?;
+                let amount: TraitValue = args[1].parse().map_err(|_| err(
+                    format!("expected a number, found '{}'", args[1])
+                ))?;
+                if amount < 1 {
+                    return Err(err(format!(
+                        "cost must be at least 1, found {}", amount
+                    )));
+                }
+                card.cost.push((id, amount));
+            }
+            "effect" => {
+                let args: Vec::<&str> = rest.split_whitespace().collect();
+                card.effects.push(parse_effect(&args, traits).map_err(err)?);
+            }
+            "trigger" => {
+                let args: Vec::<&str> = rest.split_whitespace().collect();
+                card.triggers.push(parse_trigger(&args, traits).map_err(err)?);
+            }
+            "reaction" => card.reaction = true,
+            "class" => {
+                if rest.is_empty() {
+                    return Err(err("class needs a name".into()));
+                }
+                card.class = Some(rest.to_string());
+            }
+            "script" => {
+                script.lines.push(n + 1);
+                script.src.push_str(rest);
+                script.src.push('\n');
+            }
+            _ => return Err(err(format!("unknown key '{}'", key))),
+        }
+    }
+    script.finish(cards.last_mut(), file)?;
+
+    Ok(cards)
+}
+
+// Deck lists name one card per line, optionally preceded by a number of
+// copies, as in `3 Strike`. Blank lines and `#` comments are ignored.
+// Lists come from players over the network, so a list of more than
+// `MAX_DECK_LIST` cards is refused before anything is allocated.
+pub const MAX_DECK_LIST: usize = 1000;
+
+pub fn load_deck(
+    path: &Path,
+    cards: &[Card]
+) -> Result<Vec::<CardID>, LoadError> {
+    let file = path.display().to_string();
+    match fs::read_to_string(path) {
+        Ok(src) => parse_deck(&src, &file, cards),
+        Err(e) => Err(LoadError {
+            file,
+            line: 0,
+            message: e.to_string(),
+        }),
+    }
+}
+
+pub fn parse_deck(
+    src: &str,
+    file: &str,
+    cards: &[Card]
+) -> Result<Vec::<CardID>, LoadError> {
+    let mut list = Vec::new();
+
+    for (n, text) in src.lines().enumerate() {
+        let err = |message: String| LoadError {
+            file: file.to_string(),
+            line: n + 1,
+            message,
+        };
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let (count, name) = match text.split_once(char::is_whitespace) {
+            Some((count, name)) => match count.parse::<usize>() {
+                Ok(count) => (count, name.trim()),
+                Err(_) => (1, text),
+            },
+            None => (1, text),
+        };
+        let id = cards.iter()
+            .position(|card| card.name == name)
+            .ok_or_else(|| err(format!("unknown card '{}'", name)))?;
+        if count > MAX_DECK_LIST - list.len() {
+            return Err(err(format!(
+                "deck has more than {} cards", MAX_DECK_LIST
+            )));
+        }
+        list.extend(std::iter::repeat_n(id as CardID, count));
+    }
+
+    Ok(list)
+}
+
+// Why the rules given on a command line could not be built.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RulesError {
+    // A trait or refill spec that is not `NAME=NUMBER`.
+    BadSpec(String),
+    UnknownTrait(String),
+    // Defeat needs a trait, but none was declared.
+    NoDefeatTrait,
+    Cards(LoadError),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulesError::BadSpec(spec) => write!(
+                f, "expected NAME=NUMBER, found '{}'", spec
+            ),
+            RulesError::UnknownTrait(name) => write!(
+                f, "unknown trait '{}'", name
+            ),
+            RulesError::NoDefeatTrait => write!(
+                f, "no trait to decide defeat; declare one with --trait"
+            ),
+            RulesError::Cards(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// Rules as the command-line tools take them: traits declared as
+// `NAME=DEFAULT`, refills resetting a trait at the start of each turn as
+// `NAME=VALUE`, cards from `card_file`, and defeat on trait `defeat`, by
+// default the first trait.
+pub fn load_rules(
+    card_file: &Path,
+    trait_specs: &[String],
+    refill_specs: &[String],
+    defeat: Option<&str>
+) -> Result<Rules, RulesError> {
+    let traits = parse_traits(trait_specs, refill_specs)?;
+    let defeat = match defeat {
+        Some(name) => find_trait(&traits, name)?,
+        None if !traits.is_empty() => 0,
+        None => return Err(RulesError::NoDefeatTrait),
+    };
+
+    let cards = load_cards(card_file, &traits).map_err(RulesError::Cards)?;
+    let mut rules = Rules::new(Arc::new(cards), Arc::new(Vec::new()));
+    rules.trait_list = Arc::new(traits);
+    rules.end_conditions.push(EndCondition::Defeat { id: defeat });
+    Ok(rules)
+}
+
+pub fn parse_traits(
+    trait_specs: &[String],
+    refill_specs: &[String]
+) -> Result<Vec::<Trait>, RulesError> {
+    let mut traits = Vec::new();
+    for spec in trait_specs {
+        let (name, default) = split_spec(spec)?;
+        let mut t = Trait::new(name);
+        t.default = default;
+        traits.push(t);
+    }
+    for spec in refill_specs {
+        let (name, value) = split_spec(spec)?;
+        let id = find_trait(&traits, name)?;
+        traits[id as usize].refill = Some(Refill::Set(value));
+    }
+    Ok(traits)
+}
+
+fn split_spec(spec: &str) -> Result<(&str, TraitValue), RulesError> {
+    let bad = || RulesError::BadSpec(spec.to_string());
+    let (name, value) = spec.split_once('=').ok_or_else(bad)?;
+    Ok((name, value.parse().map_err(|_| bad())?))
+}
+
+fn find_trait(traits: &[Trait], name: &str) -> Result<TraitID, RulesError> {
+    traits.iter()
+        .position(|t| t.name == name)
+        .map(|id| id as TraitID)
+        .ok_or_else(|| RulesError::UnknownTrait(name.to_string()))
+}
+
+#[derive(Default)]
+struct PendingScript {
+    lines: Vec::<usize>,
+    src: String,
+}
+
+impl PendingScript {
+    fn finish(
+        &mut self,
+        card: Option<&mut Card>,
+        file: &str
+    ) -> Result<(), LoadError> {
+        let card = match card {
+            Some(card) if !self.lines.is_empty() => card,
+            _ => return Ok(()),
+        };
+        match Script::compile(&self.src) {
+            Ok(script) => card.effects.push(Effect::Script(Arc::new(script))),
+            Err(ScriptError::Compile { line, message }) => {
+                return Err(LoadError {
+                    file: file.to_string(),
+                    line: self.lines.get(line - 1)
+                        .or(self.lines.last())
+                        .copied()
+                        .unwrap_or(0),
+                    message,
+                });
+            }
+            Err(e) => return Err(LoadError {
+                file: file.to_string(),
+                line: self.lines[0],
+                message: e.to_string(),
+            }),
+        }
+        self.lines.clear();
+        self.src.clear();
+        Ok(())
+    }
+}
+
+fn parse_trigger(args: &[&str], traits: &[Trait]) -> Result<Trigger, String> {
+    match args {
+        ["turn-start"] => Ok(Trigger::TurnStarted),
+        ["turn-end"] => Ok(Trigger::TurnEnded),
+        ["card-played"] => Ok(Trigger::CardPlayed),
+        ["defeated"] => Ok(Trigger::Defeated),
+        ["below", id, threshold] => Ok(Trigger::TraitBelow {
+            id: parse_trait(id, traits)?,
+            threshold: threshold.parse().map_err(|_| format!(
+                "expected a number, found '{}'", threshold
+            ))?,
+        }),
+        [other, ..] => Err(format!("unknown trigger '{}'", other)),
+        [] => Err("missing trigger".into()),
+    }
+}
+
+fn parse_effect(args: &[&str], traits: &[Trait]) -> Result<Effect, String> {
+    if args == ["counter"] {
+        return Ok(Effect::Counter);
+    }
+    let arity = match args.first() {
+        Some(&"modify") | Some(&"set") => 4,
+        Some(&"draw") | Some(&"discard") => 3,
+        Some(other) => return Err(format!("unknown effect '{}'", other)),
+        None => return Err("missing effect".into()),
+    };
+    if args.len() != arity {
+        return Err(format!(
+            "effect '{}' takes {} arguments", args[0], arity - 1
+        ));
+    }
+
+    let who = match args[1] {
+        "player" => Who::Player,
+        "target" => Who::Target,
+        other => return Err(format!(
+            "expected 'player' or 'target', found '{}'", other
+        )),
+    };
+    let number = |s: &str| s.parse::<i64>()
+        .map_err(|_| format!("expected a number, found '{}'", s));
+    let count = |s: &str| s.parse::<usize>()
+        .map_err(|_| format!("expected a count, found '{}'", s));
+
+    Ok(match args[0] {
+        "modify" => Effect::ModifyTrait {
+            who,
+            id: parse_trait(args[2], traits)?,
+            by: number(args[3])?,
+        },
+        "set" => Effect::SetTrait {
+            who,
+            id: parse_trait(args[2], traits)?,
+            value: number(args[3])?,
+        },
+        "draw" => Effect::Draw { who, count: count(args[2])? },
+        _ => Effect::Discard { who, count: count(args[2])? },
+    })
+}
+
+fn parse_trait(name: &str, traits: &[Trait]) -> Result<TraitID, String> {
+    if let Some(i) = traits.iter().position(|t| t.name == name) {
+        return Ok(i as TraitID);
+    }
+    name.parse().map_err(|_| format!("unknown trait '{}'", name))
+}
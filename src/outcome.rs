@@ -0,0 +1,176 @@
+use crate::{
+    ActionError, CharacterIdx, Encounter, Event, FeatureIdx, Phase, TraitID,
+};
+
+// Ways an encounter can finish. Conditions are checked in order after
+// every action, and the first one that holds decides the outcome.
+#[derive(Clone, Copy)]
+pub enum EndCondition {
+    // A side is out once each of its characters has trait `id` at or
+    // below zero; the last side standing wins.
+    Defeat {
+        id: TraitID,
+    },
+    // `side` wins once `rounds` full rounds have passed.
+    Survive {
+        side: usize,
+        rounds: u64,
+    },
+    // `side` wins once the feature is destroyed.
+    FeatureDestroyed {
+        feature: FeatureIdx,
+        side: usize,
+    },
+    Custom(fn(&Encounter) -> Option<Outcome>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndReason {
+    Defeat,
+    Survived,
+    FeatureDestroyed,
+    Conceded,
+    Custom(String),
+}
+
+// `winner` is the winning side, or `None` for a draw.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub winner: Option<usize>,
+    pub reason: EndReason,
+}
+
+impl Encounter {
+    pub fn destroy_feature(&mut self, fid: FeatureIdx) -> bool {
+        if fid >= self.features.len() || self.is_destroyed(fid) {
+            return false;
+        }
+
+        self.destroyed.push(fid);
+        self.emit(Event::FeatureDestroyed { feature: fid });
+        true
+    }
+
+    pub fn is_destroyed(&self, fid: FeatureIdx) -> bool {
+        self.destroyed.contains(&fid)
+    }
+
+    // Ends the encounter with the first end condition that holds.
+    pub fn check_end(&mut self) -> bool {
+        if self.done {
+            return true;
+        }
+
+        let conditions = self.end_conditions.clone();
+        for condition in conditions {
+            if let Some(outcome) = self.evaluate(condition) {
+                self.finish(Some(outcome));
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Takes the character out of the encounter: it loses its turns and
+    // its place in response windows, and no longer keeps its side
+    // standing. The others play on while two or more sides have
+    // characters left; otherwise the encounter ends in favor of the side
+    // still standing, or as a draw when there is none.
+    pub fn concede(&mut self, cid: CharacterIdx) -> Result<(), ActionError> {
+        if self.done {
+            return Err(ActionError::EncounterOver);
+        }
+        if cid >= self.characters.len() {
+            return Err(ActionError::NoSuchCharacter(cid));
+        }
+        if self.has_conceded(cid) {
+            return Err(ActionError::Conceded(cid));
+        }
+        self.conceded.push(cid);
+        self.emit(Event::Conceded { character: cid });
+
+        let mut sides: Vec::<usize> = (0..self.characters.len())
+            .filter(|&other| !self.has_conceded(other))
+            .map(|other| self.characters[other].side)
+            .collect();
+        sides.sort();
+        sides.dedup();
+        if sides.len() < 2 {
+            self.finish(Some(Outcome {
+                winner: sides.first().copied(),
+                reason: EndReason::Conceded,
+            }));
+            return Ok(());
+        }
+
+        let closed = match self.window.as_mut() {
+            Some(window) => {
+                window.waiting.retain(|&other| other != cid);
+                window.waiting.is_empty()
+            }
+            None => false,
+        };
+        if closed {
+            self.window = None;
+            self.resolve_stack();
+        } else if self.window.is_none()
+            && self.active == cid
+            && self.phase == Phase::Main
+        {
+            self.finish_turn();
+        }
+        self.dispatch();
+        self.check_end();
+        Ok(())
+    }
+
+    pub fn has_conceded(&self, cid: CharacterIdx) -> bool {
+        self.conceded.contains(&cid)
+    }
+
+    fn evaluate(&self, condition: EndCondition) -> Option<Outcome> {
+        match condition {
+            EndCondition::Defeat { id } => {
+                let mut sides: Vec::<usize> = self.characters.iter()
+                    .map(|ch| ch.side)
+                    .collect();
+                sides.sort();
+                sides.dedup();
+                let standing: Vec::<usize> = sides.iter().copied()
+                    .filter(|&side| (0..self.characters.len()).any(|cid| {
+                        self.characters[cid].side == side
+                            && !self.has_conceded(cid)
+                            && self.get_trait(cid, id) > 0
+                    }))
+                    .collect();
+                if sides.len() < 2 || standing.len() > 1 {
+                    return None;
+                }
+                Some(Outcome {
+                    winner: standing.first().copied(),
+                    reason: EndReason::Defeat,
+                })
+            }
+            EndCondition::Survive { side, rounds } => {
+                if self.round <= rounds {
+                    return None;
+                }
+                Some(Outcome {
+                    winner: Some(side),
+                    reason: EndReason::Survived,
+                })
+            }
+            EndCondition::FeatureDestroyed { feature, side } => {
+                if !self.is_destroyed(feature) {
+                    return None;
+                }
+                Some(Outcome {
+                    winner: Some(side),
+                    reason: EndReason::FeatureDestroyed,
+                })
+            }
+            EndCondition::Custom(predicate) => predicate(self),
+        }
+    }
+}
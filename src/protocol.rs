@@ -0,0 +1,342 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{
+    ActionError, CharacterIdx, Command, Event, FeatureIdx, Game, GameView,
+    Outcome, PlayerID,
+};
+
+// Messages between a client and a server hosting a `Game`. Each message
+// is sent as one frame: a little-endian u32 body length followed by the
+// bincode encoding of a `Request` or `Response`.
+//
+// The first frame a client sends must be `Hello` with its version. The
+// server answers `Welcome` when it speaks the same version, or else
+// `Error(VersionMismatch)` and closes the connection. `Join` then names
+// the player the session acts as, unless the server identifies players
+// by their client certificates: it then joins the session as the
+// certificate's player before answering `Welcome`, or sends
+// `Error(SeatTaken)` and closes the connection if that player is already
+// connected, and refuses `Join`.
+//
+// Games are played in rooms. A player joins a room with a deck list in
+// the `loader::parse_deck` format and marks ready, or queues for a
+// two-player match, and every member of a room is sent a `Room` update
+// whenever it changes. Once all seats are filled and ready the game
+// starts: each player gets their `State`, then an `Event` for each
+// visible event and a fresh `State` after every accepted action, ending
+// with `GameOver`, after which the room is closed.
+//
+// Bump this whenever a message changes shape.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+// Frames with a longer body are rejected without reading them.
+pub const MAX_FRAME: usize = 1 << 20;
+
+const HEADER: usize = 4;
+
+pub type RoomID = u64;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Request {
+    Hello {
+        version: u32,
+    },
+    Join {
+        player: PlayerID,
+    },
+    PlayCard {
+        target: CharacterIdx,
+        index: usize,
+    },
+    ActivateFeature {
+        feature: FeatureIdx,
+    },
+    EndTurn,
+    Respond {
+        target: CharacterIdx,
+        index: usize,
+    },
+    RespondFeature {
+        feature: FeatureIdx,
+    },
+    Pass,
+    Concede,
+    ListRooms,
+    CreateRoom {
+        seats: usize,
+    },
+    JoinRoom {
+        room: RoomID,
+        deck: String,
+    },
+    Ready,
+    LeaveRoom,
+    Queue {
+        deck: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub id: RoomID,
+    pub seats: usize,
+    // Members in seat order, with whether each is ready.
+    pub members: Vec::<(PlayerID, bool)>,
+    pub started: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Response {
+    Welcome {
+        version: u32,
+    },
+    State(GameView),
+    Event(Event),
+    Error(ProtocolError),
+    GameOver {
+        outcome: Option<Outcome>,
+    },
+    Rooms(Vec::<RoomInfo>),
+    Room(RoomInfo),
+    // Waiting in the matchmaking queue for an opponent.
+    Queued,
+}
+
+// Why a request was refused. The connection stays open except after a
+// version mismatch. Clients decode variants by position, so new ones
+// are only ever added at the end.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtocolError {
+    Action(ActionError),
+    VersionMismatch {
+        server: u32,
+        client: u32,
+    },
+    NoHandshake,
+    NotJoined,
+    AlreadyJoined,
+    NoSuchPlayer(PlayerID),
+    Malformed(String),
+    // Another connection already plays as this player.
+    SeatTaken(PlayerID),
+    NoSuchRoom(RoomID),
+    RoomFull(RoomID),
+    InvalidSeats(usize),
+    // Already in a room or the queue.
+    InRoom,
+    NotInRoom,
+    NotInGame,
+    BadDeck(String),
+    // No accepted client certificate identifies the session.
+    Unauthenticated,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Action(err) => write!(f, "{}", err),
+            ProtocolError::VersionMismatch { server, client } => write!(
+                f, "server speaks protocol {} but client sent {}",
+                server, client
+            ),
+            ProtocolError::NoHandshake => write!(
+                f, "the first message must be a hello"
+            ),
+            ProtocolError::NotJoined => write!(f, "not joined to a game"),
+            ProtocolError::AlreadyJoined => write!(
+                f, "already joined to a game"
+            ),
+            ProtocolError::NoSuchPlayer(player) => write!(
+                f, "no player {} in this game", player
+            ),
+            ProtocolError::Malformed(msg) => write!(
+                f, "malformed message: {}", msg
+            ),
+            ProtocolError::SeatTaken(player) => write!(
+                f, "player {} is already connected", player
+            ),
+            ProtocolError::NoSuchRoom(room) => write!(
+                f, "no room {}", room
+            ),
+            ProtocolError::RoomFull(room) => write!(
+                f, "room {} is full", room
+            ),
+            ProtocolError::InvalidSeats(seats) => write!(
+                f, "a room cannot have {} seats", seats
+            ),
+            ProtocolError::InRoom => write!(
+                f, "already in a room or queued"
+            ),
+            ProtocolError::NotInRoom => write!(f, "not in a room"),
+            ProtocolError::NotInGame => write!(f, "not in a started game"),
+            ProtocolError::BadDeck(msg) => write!(f, "bad deck: {}", msg),
+            ProtocolError::Unauthenticated => write!(
+                f, "no known client certificate identifies this session"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ActionError> for ProtocolError {
+    fn from(err: ActionError) -> ProtocolError {
+        ProtocolError::Action(err)
+    }
+}
+
+impl Game {
+    // Applies a game action sent by `player`. Feature activation and
+    // ending the turn name no character, so they are refused unless it
+    // is the player's turn. Room requests are left to the server.
+    pub fn handle(
+        &mut self,
+        player: PlayerID,
+        request: &Request
+    ) -> Result<(), ProtocolError> {
+        let character = *self.players.get(&player)
+            .ok_or(ProtocolError::NoSuchPlayer(player))?;
+        let active = self.encounter.active;
+        let command = match *request {
+            Request::Hello { .. } => {
+                return Err(ProtocolError::Malformed(
+                    "hello after the handshake".to_string()
+                ));
+            }
+            Request::Join { .. } => return Err(ProtocolError::AlreadyJoined),
+            Request::ListRooms
+            | Request::CreateRoom { .. }
+            | Request::JoinRoom { .. }
+            | Request::Ready
+            | Request::LeaveRoom
+            | Request::Queue { .. } => {
+                return Err(ProtocolError::Malformed(
+                    "not a game action".to_string()
+                ));
+            }
+            Request::PlayCard { target, index } => Command::PlayCard {
+                character,
+                target,
+                index,
+            },
+            Request::ActivateFeature { .. } | Request::EndTurn
+                if character != active =>
+            {
+                return Err(ActionError::NotYourTurn { character, active }.into());
+            }
+            Request::ActivateFeature { feature } =>
+                Command::ActivateFeature { feature },
+            Request::EndTurn => Command::EndTurn,
+            Request::Respond { target, index } => Command::Respond {
+                character,
+                target,
+                index,
+            },
+            Request::RespondFeature { feature } => Command::RespondFeature {
+                character,
+                feature,
+            },
+            Request::Pass => Command::Pass { character },
+            Request::Concede => Command::Concede { character },
+        };
+        self.apply(command)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    Io(io::Error),
+    TooLarge(usize),
+    Malformed(String),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Io(err) => write!(f, "{}", err),
+            FrameError::TooLarge(len) => write!(
+                f, "frame of {} bytes exceeds the limit of {}",
+                len, MAX_FRAME
+            ),
+            FrameError::Malformed(msg) => write!(
+                f, "malformed frame: {}", msg
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(err: io::Error) -> FrameError {
+        FrameError::Io(err)
+    }
+}
+
+// One complete frame holding `message`.
+pub fn encode<T: Serialize>(message: &T) -> Result<Vec::<u8>, FrameError> {
+    let body = bincode::serialize(message)
+        .map_err(|err| FrameError::Malformed(err.to_string()))?;
+    if body.len() > MAX_FRAME {
+        return Err(FrameError::TooLarge(body.len()));
+    }
+
+    let mut frame = Vec::with_capacity(HEADER + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+// Decodes the frame at the start of `buf` and returns the message with
+// the number of bytes it took, or `None` while the frame is incomplete.
+// Meant for non-blocking sockets that buffer whatever has arrived.
+pub fn decode<T: DeserializeOwned>(
+    buf: &[u8]
+) -> Result<Option<(T, usize)>, FrameError> {
+    if buf.len() < HEADER {
+        return Ok(None);
+    }
+    let len = frame_len(&buf[..HEADER])?;
+    if buf.len() < HEADER + len {
+        return Ok(None);
+    }
+
+    let message = bincode::deserialize(&buf[HEADER..HEADER + len])
+        .map_err(|err| FrameError::Malformed(err.to_string()))?;
+    Ok(Some((message, HEADER + len)))
+}
+
+pub fn write_frame<T: Serialize, W: Write>(
+    writer: &mut W,
+    message: &T
+) -> Result<(), FrameError> {
+    writer.write_all(&encode(message)?)?;
+    Ok(())
+}
+
+// Blocks until a whole frame has been read.
+pub fn read_frame<T: DeserializeOwned, R: Read>(
+    reader: &mut R
+) -> Result<T, FrameError> {
+    let mut header = [0u8; HEADER];
+    reader.read_exact(&mut header)?;
+    let len = frame_len(&header)?;
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    bincode::deserialize(&body)
+        .map_err(|err| FrameError::Malformed(err.to_string()))
+}
+
+fn frame_len(header: &[u8]) -> Result<usize, FrameError> {
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]])
+        as usize;
+    if len > MAX_FRAME {
+        return Err(FrameError::TooLarge(len));
+    }
+    Ok(len)
+}
@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::status::StatusEffect;
+use crate::{
+    ActionError, CardID, Character, CharacterIdx, Deck, Encounter,
+    FeatureID, FeatureIdx, Game, Player, Rules, StackEntry, TraitID,
+    TraitValue,
+};
+
+// Bump this whenever the text format or `checksum` changes, since older
+// replays would otherwise fail to parse or verify.
+pub const REPLAY_VERSION: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Command {
+    PlayCard {
+        character: CharacterIdx,
+        target: CharacterIdx,
+        index: usize,
+    },
+    ActivateFeature {
+        feature: FeatureIdx,
+    },
+    EndTurn,
+    Respond {
+        character: CharacterIdx,
+        target: CharacterIdx,
+        index: usize,
+    },
+    RespondFeature {
+        character: CharacterIdx,
+        feature: FeatureIdx,
+    },
+    Pass {
+        character: CharacterIdx,
+    },
+    Concede {
+        character: CharacterIdx,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Setup {
+    pub players: Vec::<Player>,
+    pub features: Vec::<FeatureID>,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Step {
+    pub command: Command,
+    pub accepted: bool,
+    pub checksum: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub setup: Setup,
+    pub steps: Vec::<Step>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize,
+    pub expected: Step,
+    pub found: Step,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "replay diverged at step {}: expected {:?}, found {:?}",
+            self.step, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// FNV-1a over everything that can influence later play, so that two
+// encounters with equal checksums behave identically from here on.
+pub fn checksum(encounter: &Encounter) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(encounter.round);
+    hash.write(encounter.active as u64);
+    hash.write(encounter.phase as u64);
+    hash.write(encounter.done as u64);
+    hash.write(encounter.rng.state());
+    hash.write(encounter.events().len() as u64);
+    hash.write_all(&encounter.features);
+    hash.write(encounter.destroyed.len() as u64);
+    for &fid in encounter.destroyed.iter() {
+        hash.write(fid as u64);
+    }
+    hash.write(encounter.conceded.len() as u64);
+    for &cid in encounter.conceded.iter() {
+        hash.write(cid as u64);
+    }
+    hash.write(encounter.stack().len() as u64);
+    for entry in encounter.stack() {
+        match entry {
+            StackEntry::Card {
+                character, targets, feature, card, kind
+            } => {
+                hash.write(*character as u64);
+                hash.write(targets.len() as u64);
+                for &target in targets.iter() {
+                    hash.write(target as u64);
+                }
+                hash.write(feature.map_or(u64::MAX, |f| f as u64));
+                hash.write(*card);
+                hash.write(*kind);
+            }
+            StackEntry::Feature { character, feature, id } => {
+                hash.write(*character as u64);
+                hash.write(*feature as u64);
+                hash.write(*id);
+            }
+        }
+    }
+    let waiting = encounter.window().map_or(&[][..], |w| &w.waiting);
+    hash.write(encounter.window().is_some() as u64);
+    hash.write(waiting.len() as u64);
+    for &cid in waiting {
+        hash.write(cid as u64);
+    }
+    for character in encounter.characters.iter() {
+        hash.write(character.side as u64);
+        let mut traits: Vec::<_> = character.traits.iter().collect();
+        traits.sort();
+        hash.write(traits.len() as u64);
+        for (&id, &value) in traits {
+            hash.write(id);
+            hash.write(value as u64);
+        }
+        hash.write(character.statuses.len() as u64);
+        for status in character.statuses.iter() {
+            hash.write(status.id);
+            hash.write(status.stacks as u64);
+            hash.write(status.turns.map_or(u64::MAX, |t| t as u64));
+        }
+        let deck = &character.deck;
+        hash.write(deck.clist.len() as u64);
+        for slot in deck.clist.iter() {
+            hash.write(slot.map_or(u64::MAX, |n| n));
+        }
+        hash.write_all(&deck.deck);
+        hash.write_all(&deck.hand);
+        hash.write_all(&deck.discard);
+    }
+    hash.0
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_all(&mut self, values: &[u64]) {
+        self.write(values.len() as u64);
+        for &value in values {
+            self.write(value);
+        }
+    }
+}
+
+impl Encounter {
+    // Carries out a command without recording it.
+    pub fn execute(&mut self, command: Command) -> Result<(), ActionError> {
+        match command {
+            Command::PlayCard { character, target, index } =>
+                self.play_card(character, target, index),
+            Command::ActivateFeature { feature } =>
+                self.activate_feature(feature),
+            Command::EndTurn => self.end_turn(),
+            Command::Respond { character, target, index } =>
+                self.respond(character, target, index),
+            Command::RespondFeature { character, feature } =>
+                self.respond_feature(character, feature),
+            Command::Pass { character } => self.pass(character),
+            Command::Concede { character } => self.concede(character),
+        }
+    }
+}
+
+impl Game {
+    pub fn apply(&mut self, command: Command) -> Result<(), ActionError> {
+        let before = self.history.before(
+            &self.encounter,
+            self.recording.steps.len()
+        );
+        let result = self.encounter.execute(command);
+        self.recording.steps.push(Step {
+            command,
+            accepted: result.is_ok(),
+            checksum: checksum(&self.encounter),
+        });
+        if result.is_ok() {
+            self.history.commit(before, &self.encounter);
+        }
+        result
+    }
+}
+
+impl Recording {
+    pub fn replay(
+        &self,
+        rules: &Rules
+    ) -> Game {
+        let mut game = self.new_game(rules);
+        for step in self.steps.iter() {
+            let _ = game.apply(step.command);
+        }
+        game
+    }
+
+    pub fn verify(
+        &self,
+        rules: &Rules
+    ) -> Result<Game, Divergence> {
+        let mut game = self.new_game(rules);
+        for (i, expected) in self.steps.iter().enumerate() {
+            let _ = game.apply(expected.command);
+            let found = game.recording.steps[i];
+            if found != *expected {
+                return Err(Divergence {
+                    step: i,
+                    expected: *expected,
+                    found,
+                });
+            }
+        }
+        Ok(game)
+    }
+
+    fn new_game(
+        &self,
+        rules: &Rules
+    ) -> Game {
+        Game::new(
+            self.setup.players.clone(),
+            self.setup.features.clone(),
+            rules,
+            self.setup.seed
+        )
+    }
+
+    pub fn load(path: &Path) -> io::Result<Recording> {
+        let src = fs::read_to_string(path)?;
+        Recording::parse(&src).map_err(
+            |e| io::Error::new(io::ErrorKind::InvalidData, e)
+        )
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(src: &str) -> Result<Recording, ParseError> {
+        let mut recording = Recording {
+            setup: Setup {
+                players: Vec::new(),
+                features: Vec::new(),
+                seed: 0,
+            },
+            steps: Vec::new(),
+        };
+        let mut seen_header = false;
+
+        for (n, text) in src.lines().enumerate() {
+            let line = n + 1;
+            let err = |message: String| ParseError { line, message };
+            let mut words = text.split_whitespace();
+            let key = match words.next() {
+                Some(key) if !key.starts_with('#') => key,
+                _ => continue,
+            };
+            let args: Vec::<&str> = words.collect();
+
+            if !seen_header {
+                if key != "kier-replay" {
+                    return Err(err("missing kier-replay header".into()));
+                }
+                let version: u32 = parse_arg(&args, 0, line)?;
+                if version != REPLAY_VERSION {
+                    return Err(err(format!(
+                        "unsupported replay version {}", version
+                    )));
+                }
+                seen_header = true;
+                continue;
+            }
+
+            let setup = &mut recording.setup;
+            match key {
+                "seed" => setup.seed = parse_arg(&args, 0, line)?,
+                "feature" => setup.features.push(parse_arg(&args, 0, line)?),
+                "player" => setup.players.push(Player {
+                    id: parse_arg(&args, 0, line)?,
+                    character: Character::new(
+                        HashMap::new(),
+                        Deck {
+                            clist: Vec::new(),
+                            deck: Vec::new(),
+                            hand: Vec::new(),
+                            discard: Vec::new(),
+                        }
+                    ),
+                }),
+                "side" | "trait" | "status" | "clist" | "deck" | "hand" | "discard" => {
+                    let character = match setup.players.last_mut() {
+                        Some(player) => &mut player.character,
+                        None => return Err(err(format!(
+                            "'{}' before any player", key
+                        ))),
+                    };
+                    match key {
+                        "side" => character.side = parse_arg(&args, 0, line)?,
+                        "trait" => {
+                            let id: TraitID = parse_arg(&args, 0, line)?;
+                            let value: TraitValue
+                                = parse_arg(&args, 1, line)?;
+                            character.traits.insert(id, value);
+                        }
+                        "status" => {
+                            let turns = match args.get(2) {
+                                Some(&"-") => None,
+                                _ => Some(parse_arg(&args, 2, line)?),
+                            };
+                            character.statuses.push(StatusEffect {
+                                id: parse_arg(&args, 0, line)?,
+                                stacks: parse_arg(&args, 1, line)?,
+                                turns,
+                            });
+                        }
+                        "clist" => {
+                            for (i, word) in args.iter().enumerate() {
+                                character.deck.clist.push(match *word {
+                                    "-" => None,
+                                    _ => Some(parse_arg(&args, i, line)?),
+                                });
+                            }
+                        }
+                        _ => {
+                            let mut cards: Vec::<CardID> = Vec::new();
+                            for i in 0..args.len() {
+                                cards.push(parse_arg(&args, i, line)?);
+                            }
+                            let deck = &mut character.deck;
+                            match key {
+                                "deck" => deck.deck = cards,
+                                "hand" => deck.hand = cards,
+                                _ => deck.discard = cards,
+                            }
+                        }
+                    }
+                }
+                "play" | "activate" | "end" | "respond"
+                | "respond-feature" | "pass" | "concede" => {
+                    let (command, rest) = match key {
+                        "play" => (Command::PlayCard {
+                            character: parse_arg(&args, 0, line)?,
+                            target: parse_arg(&args, 1, line)?,
+                            index: parse_arg(&args, 2, line)?,
+                        }, 3),
+                        "activate" => (Command::ActivateFeature {
+                            feature: parse_arg(&args, 0, line)?,
+                        }, 1),
+                        "respond" => (Command::Respond {
+                            character: parse_arg(&args, 0, line)?,
+                            target: parse_arg(&args, 1, line)?,
+                            index: parse_arg(&args, 2, line)?,
+                        }, 3),
+                        "respond-feature" => (Command::RespondFeature {
+                            character: parse_arg(&args, 0, line)?,
+                            feature: parse_arg(&args, 1, line)?,
+                        }, 2),
+                        "pass" => (Command::Pass {
+                            character: parse_arg(&args, 0, line)?,
+                        }, 1),
+                        "concede" => (Command::Concede {
+                            character: parse_arg(&args, 0, line)?,
+                        }, 1),
+                        _ => (Command::EndTurn, 0),
+                    };
+                    let accepted = match args.get(rest) {
+                        Some(&"ok") => true,
+                        Some(&"rejected") => false,
+                        _ => return Err(err(
+                            "expected 'ok' or 'rejected'".into()
+                        )),
+                    };
+                    let checksum = args.get(rest + 1)
+                        .and_then(|s| u64::from_str_radix(s, 16).ok())
+                        .ok_or_else(|| err("expected checksum".into()))?;
+                    recording.steps.push(Step {
+                        command,
+                        accepted,
+                        checksum,
+                    });
+                }
+                _ => return Err(err(format!("unknown entry '{}'", key))),
+            }
+        }
+
+        if !seen_header {
+            return Err(ParseError {
+                line: 0,
+                message: "empty replay".into(),
+            });
+        }
+
+        Ok(recording)
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(
+    args: &[&str],
+    i: usize,
+    line: usize
+) -> Result<T, ParseError> {
+    args.get(i)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| ParseError {
+            line,
+            message: format!("missing or invalid argument {}", i + 1),
+        })
+}
+
+fn write_cards(
+    f: &mut fmt::Formatter,
+    key: &str,
+    cards: &[CardID]
+) -> fmt::Result {
+    write!(f, "{}", key)?;
+    for card in cards {
+        write!(f, " {}", card)?;
+    }
+    writeln!(f)
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "kier-replay {}", REPLAY_VERSION)?;
+        writeln!(f, "seed {}", self.setup.seed)?;
+        for feature in self.setup.features.iter() {
+            writeln!(f, "feature {}", feature)?;
+        }
+        for player in self.setup.players.iter() {
+            writeln!(f, "player {}", player.id)?;
+            let character = &player.character;
+            writeln!(f, "side {}", character.side)?;
+            let mut traits: Vec::<_> = character.traits.iter().collect();
+            traits.sort();
+            for (id, value) in traits {
+                writeln!(f, "trait {} {}", id, value)?;
+            }
+            for status in character.statuses.iter() {
+                write!(f, "status {} {}", status.id, status.stacks)?;
+                match status.turns {
+                    Some(turns) => writeln!(f, " {}", turns)?,
+                    None => writeln!(f, " -")?,
+                }
+            }
+            write!(f, "clist")?;
+            for slot in character.deck.clist.iter() {
+                match slot {
+                    Some(n) => write!(f, " {}", n)?,
+                    None => write!(f, " -")?,
+                }
+            }
+            writeln!(f)?;
+            write_cards(f, "deck", &character.deck.deck)?;
+            write_cards(f, "hand", &character.deck.hand)?;
+            write_cards(f, "discard", &character.deck.discard)?;
+        }
+        for step in self.steps.iter() {
+            match step.command {
+                Command::PlayCard { character, target, index } => write!(
+                    f, "play {} {} {}", character, target, index
+                )?,
+                Command::ActivateFeature { feature } =>
+                    write!(f, "activate {}", feature)?,
+                Command::EndTurn => write!(f, "end")?,
+                Command::Respond { character, target, index } => write!(
+                    f, "respond {} {} {}", character, target, index
+                )?,
+                Command::RespondFeature { character, feature } => write!(
+                    f, "respond-feature {} {}", character, feature
+                )?,
+                Command::Pass { character } =>
+                    write!(f, "pass {}", character)?,
+                Command::Concede { character } =>
+                    write!(f, "concede {}", character)?,
+            }
+            writeln!(
+                f,
+                " {} {:016x}",
+                if step.accepted { "ok" } else { "rejected" },
+                step.checksum
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,764 @@
+use std::fmt;
+
+use crate::{CharacterIdx, Encounter, FeatureIdx, TraitValue};
+
+// A tiny integer scripting language for card and feature effects.
+//
+//     let hp = trait(target, 0);
+//     if hp < 10 { modify(target, 0, -5); } else { draw(player, 1); }
+//     for c in 0..characters() {
+//         if c != player { modify(c, 0, -1); }
+//     }
+//
+// Every value is an i64, with 0 meaning false. `player`, `target` and
+// `feature` are predefined; `feature` is the feature a card was played
+// on, or -1 for cards targeting characters, and `player` and `target`
+// are the active character for features.
+// Scripts compile to bytecode and run under a step limit, and can only
+// touch the encounter through the builtins below.
+const BUILTINS: &[(&str, Builtin, usize)] = &[
+    ("characters", Builtin::Characters, 0),
+    ("features", Builtin::Features, 0),
+    ("active", Builtin::Active, 0),
+    ("round", Builtin::Round, 0),
+    ("random", Builtin::Random, 1),
+    ("side", Builtin::Side, 1),
+    ("hand", Builtin::Hand, 1),
+    ("deck", Builtin::Deck, 1),
+    ("discard_pile", Builtin::DiscardPile, 1),
+    ("feature_id", Builtin::FeatureId, 1),
+    ("trait", Builtin::Trait, 2),
+    ("set_trait", Builtin::SetTrait, 3),
+    ("modify", Builtin::Modify, 3),
+    ("draw", Builtin::Draw, 2),
+    ("discard", Builtin::Discard, 2),
+];
+
+const KEYWORDS: &[&str] = &["let", "if", "else", "while", "for", "in"];
+
+const MAX_NESTING: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Builtin {
+    Characters,
+    Features,
+    Active,
+    Round,
+    Random,
+    Side,
+    Hand,
+    Deck,
+    DiscardPile,
+    FeatureId,
+    Trait,
+    SetTrait,
+    Modify,
+    Draw,
+    Discard,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Op {
+    Push(i64),
+    Load(usize),
+    Store(usize),
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Neg,
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Jump(usize),
+    JumpIfZero(usize),
+    Call(Builtin),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptError {
+    Compile {
+        line: usize,
+        message: String,
+    },
+    StepLimit,
+    DivideByZero,
+    BadArgument {
+        builtin: &'static str,
+        value: i64,
+    },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Compile { line, message } => write!(
+                f, "line {}: {}", line, message
+            ),
+            ScriptError::StepLimit => write!(f, "script step limit reached"),
+            ScriptError::DivideByZero => write!(f, "division by zero"),
+            ScriptError::BadArgument { builtin, value } => write!(
+                f, "bad argument {} to {}", value, builtin
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Script {
+    code: Vec::<Op>,
+    slots: usize,
+}
+
+impl Script {
+    pub fn compile(src: &str) -> Result<Script, ScriptError> {
+        let mut compiler = Compiler {
+            tokens: lex(src)?,
+            pos: 0,
+            code: Vec::new(),
+            scopes: vec![vec![
+                ("player".to_string(), 0),
+                ("target".to_string(), 1),
+                ("feature".to_string(), 2),
+            ]],
+            slots: 3,
+            depth: 0,
+        };
+        while !compiler.at_end() {
+            compiler.statement()?;
+        }
+        Ok(Script {
+            code: compiler.code,
+            slots: compiler.slots,
+        })
+    }
+
+    pub fn run(
+        &self,
+        encounter: &mut Encounter,
+        player: CharacterIdx,
+        target: CharacterIdx,
+        feature: Option<FeatureIdx>
+    ) -> Result<(), ScriptError> {
+        let limit = encounter.script_step_limit;
+        let mut steps: u64 = 0;
+        let mut vars = vec![0i64; self.slots];
+        vars[0] = player as i64;
+        vars[1] = target as i64;
+        vars[2] = feature.map_or(-1, |f| f as i64);
+        let mut stack: Vec::<i64> = Vec::new();
+        let mut pc = 0;
+
+        while let Some(&op) = self.code.get(pc) {
+            steps += 1;
+            if steps > limit {
+                return Err(ScriptError::StepLimit);
+            }
+            pc += 1;
+
+            match op {
+                Op::Push(v) => stack.push(v),
+                Op::Load(slot) => stack.push(vars[slot]),
+                Op::Store(slot) => vars[slot] = pop(&mut stack),
+                Op::Pop => {
+                    pop(&mut stack);
+                }
+                Op::Neg => {
+                    let a = pop(&mut stack);
+                    stack.push(a.wrapping_neg());
+                }
+                Op::Not => {
+                    let a = pop(&mut stack);
+                    stack.push((a == 0) as i64);
+                }
+                Op::Jump(to) => pc = to,
+                Op::JumpIfZero(to) => {
+                    if pop(&mut stack) == 0 {
+                        pc = to;
+                    }
+                }
+                Op::Call(builtin) => {
+                    let result = call(
+                        builtin,
+                        encounter,
+                        &mut stack,
+                        &mut steps,
+                        limit
+                    )?;
+                    stack.push(result);
+                }
+                _ => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    stack.push(match op {
+                        Op::Add => a.wrapping_add(b),
+                        Op::Sub => a.wrapping_sub(b),
+                        Op::Mul => a.wrapping_mul(b),
+                        Op::Div | Op::Rem if b == 0 => {
+                            return Err(ScriptError::DivideByZero);
+                        }
+                        Op::Div => a.wrapping_div(b),
+                        Op::Rem => a.wrapping_rem(b),
+                        Op::Eq => (a == b) as i64,
+                        Op::Ne => (a != b) as i64,
+                        Op::Lt => (a < b) as i64,
+                        Op::Le => (a <= b) as i64,
+                        Op::Gt => (a > b) as i64,
+                        _ => (a >= b) as i64,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The compiler only emits balanced code, so an empty stack cannot occur.
+fn pop(stack: &mut Vec::<i64>) -> i64 {
+    stack.pop().unwrap_or(0)
+}
+
+fn builtin_name(builtin: Builtin) -> &'static str {
+    BUILTINS.iter()
+        .find(|(_, b, _)| *b == builtin)
+        .map_or("?", |(name, _, _)| name)
+}
+
+fn call(
+    builtin: Builtin,
+    encounter: &mut Encounter,
+    stack: &mut Vec::<i64>,
+    steps: &mut u64,
+    limit: u64
+) -> Result<i64, ScriptError> {
+    let bad = |value: i64| ScriptError::BadArgument {
+        builtin: builtin_name(builtin),
+        value,
+    };
+    let character = |encounter: &Encounter, value: i64| {
+        if value >= 0 && (value as usize) < encounter.characters.len() {
+            Ok(value as usize)
+        } else {
+            Err(bad(value))
+        }
+    };
+    let natural = |value: i64| {
+        if value >= 0 { Ok(value as u64) } else { Err(bad(value)) }
+    };
+    // Repeated actions cost one step each.
+    let mut charge = |n: u64| {
+        *steps = steps.saturating_add(n);
+        if *steps > limit { Err(ScriptError::StepLimit) } else { Ok(()) }
+    };
+
+    Ok(match builtin {
+        Builtin::Characters => encounter.characters.len() as i64,
+        Builtin::Features => encounter.features.len() as i64,
+        Builtin::Active => encounter.active as i64,
+        Builtin::Round => encounter.round as i64,
+        Builtin::Random => {
+            let n = natural(pop(stack))?;
+            encounter.rng.below(n) as i64
+        }
+        Builtin::Side => {
+            let cid = character(encounter, pop(stack))?;
+            encounter.characters[cid].side as i64
+        }
+        Builtin::Hand | Builtin::Deck | Builtin::DiscardPile => {
+            let cid = character(encounter, pop(stack))?;
+            let deck = &encounter.characters[cid].deck;
+            (match builtin {
+                Builtin::Hand => deck.hand.len(),
+                Builtin::Deck => deck.deck.len(),
+                _ => deck.discard.len(),
+            }) as i64
+        }
+        Builtin::FeatureId => {
+            let value = pop(stack);
+            match encounter.features.get(natural(value)? as usize) {
+                Some(&id) => id as i64,
+                None => return Err(bad(value)),
+            }
+        }
+        Builtin::Trait => {
+            let id = natural(pop(stack))?;
+            let cid = character(encounter, pop(stack))?;
+            encounter.get_trait(cid, id)
+        }
+        Builtin::SetTrait | Builtin::Modify => {
+            let value: TraitValue = pop(stack);
+            let id = natural(pop(stack))?;
+            let cid = character(encounter, pop(stack))?;
+            if builtin == Builtin::SetTrait {
+                encounter.set_trait(cid, id, value);
+            } else {
+                encounter.modify_trait(cid, id, value);
+            }
+            0
+        }
+        Builtin::Draw | Builtin::Discard => {
+            let n = natural(pop(stack))?;
+            let cid = character(encounter, pop(stack))?;
+            charge(n)?;
+            for _ in 0..n {
+                if builtin == Builtin::Draw {
+                    encounter.draw_card(cid);
+                } else {
+                    let size = encounter.characters[cid].deck.hand.len();
+                    if size == 0 {
+                        break;
+                    }
+                    let i = encounter.rng.below(size as u64) as usize;
+                    encounter.discard_card(cid, i);
+                }
+            }
+            0
+        }
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tok {
+    Num(i64),
+    Ident(String),
+    Sym(&'static str),
+}
+
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+const SYMBOLS: &[&str] = &[
+    "..", "==", "!=", "<=", ">=", "&&", "||",
+    "+", "-", "*", "/", "%", "<", ">", "!", "=",
+    "(", ")", "{", "}", ",", ";",
+];
+
+fn lex(src: &str) -> Result<Vec::<Token>, ScriptError> {
+    let mut tokens = Vec::new();
+
+    for (n, text) in src.lines().enumerate() {
+        let line = n + 1;
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with("//") {
+                break;
+            }
+
+            let c = rest.chars().next().unwrap_or(' ');
+            let len = if c.is_ascii_digit() {
+                let len = rest.find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let value = rest[..len].parse().map_err(
+                    |_| ScriptError::Compile {
+                        line,
+                        message: format!("number too large: {}", &rest[..len]),
+                    }
+                )?;
+                tokens.push(Token { tok: Tok::Num(value), line });
+                len
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                tokens.push(Token {
+                    tok: Tok::Ident(rest[..len].to_string()),
+                    line,
+                });
+                len
+            } else if let Some(sym) = SYMBOLS.iter()
+                .find(|s| rest.starts_with(*s))
+            {
+                tokens.push(Token { tok: Tok::Sym(sym), line });
+                sym.len()
+            } else {
+                return Err(ScriptError::Compile {
+                    line,
+                    message: format!("unexpected character '{}'", c),
+                });
+            };
+            rest = &rest[len..];
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Compiler {
+    tokens: Vec::<Token>,
+    pos: usize,
+    code: Vec::<Op>,
+    scopes: Vec::<Vec::<(String, usize)>>,
+    slots: usize,
+    depth: usize,
+}
+
+impl Compiler {
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |t| t.line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ScriptError> {
+        Err(ScriptError::Compile {
+            line: self.line(),
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn peek_sym(&self, sym: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Sym(s)) if *s == sym)
+    }
+
+    fn peek_keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(s)) if s == word)
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        if self.peek_sym(sym) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<(), ScriptError> {
+        if self.eat_sym(sym) {
+            return Ok(());
+        }
+        self.error(format!("expected '{}'", sym))
+    }
+
+    fn expect_keyword(&mut self, word: &str) -> Result<(), ScriptError> {
+        if self.peek_keyword(word) {
+            self.pos += 1;
+            return Ok(());
+        }
+        self.error(format!("expected '{}'", word))
+    }
+
+    fn name(&mut self) -> Result<String, ScriptError> {
+        match self.peek() {
+            Some(Tok::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.error("expected a name".into()),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|&(_, slot)| slot)
+    }
+
+    fn declare(&mut self, name: String) -> usize {
+        let slot = self.slots;
+        self.slots += 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name, slot));
+        }
+        slot
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let to = self.code.len();
+        match &mut self.code[at] {
+            Op::Jump(t) | Op::JumpIfZero(t) => *t = to,
+            _ => {}
+        }
+    }
+
+    fn nest(&mut self) -> Result<(), ScriptError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return self.error("script nested too deeply".into());
+        }
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<(), ScriptError> {
+        self.nest()?;
+        self.expect_sym("{")?;
+        self.scopes.push(Vec::new());
+        while !self.eat_sym("}") {
+            if self.at_end() {
+                return self.error("expected '}'".into());
+            }
+            self.statement()?;
+        }
+        self.scopes.pop();
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), ScriptError> {
+        if self.peek_keyword("let") {
+            self.pos += 1;
+            let name = self.name()?;
+            self.expect_sym("=")?;
+            self.expression()?;
+            self.expect_sym(";")?;
+            let slot = self.declare(name);
+            self.emit(Op::Store(slot));
+        } else if self.peek_keyword("if") {
+            self.pos += 1;
+            self.if_statement()?;
+        } else if self.peek_keyword("while") {
+            self.pos += 1;
+            let start = self.code.len();
+            self.expression()?;
+            let exit = self.emit(Op::JumpIfZero(0));
+            self.block()?;
+            self.emit(Op::Jump(start));
+            self.patch(exit);
+        } else if self.peek_keyword("for") {
+            self.pos += 1;
+            self.scopes.push(Vec::new());
+            let name = self.name()?;
+            self.expect_keyword("in")?;
+            self.expression()?;
+            self.expect_sym("..")?;
+            let var = self.declare(name);
+            self.emit(Op::Store(var));
+            self.expression()?;
+            let end = self.declare(String::new());
+            self.emit(Op::Store(end));
+
+            let start = self.emit(Op::Load(var));
+            self.emit(Op::Load(end));
+            self.emit(Op::Lt);
+            let exit = self.emit(Op::JumpIfZero(0));
+            self.block()?;
+            self.emit(Op::Load(var));
+            self.emit(Op::Push(1));
+            self.emit(Op::Add);
+            self.emit(Op::Store(var));
+            self.emit(Op::Jump(start));
+            self.patch(exit);
+            self.scopes.pop();
+        } else if matches!(
+            self.tokens.get(self.pos + 1).map(|t| &t.tok),
+            Some(Tok::Sym("="))
+        ) {
+            let name = self.name()?;
+            let slot = match self.lookup(&name) {
+                Some(slot) if slot > 2 => slot,
+                Some(_) => return self.error(format!(
+                    "cannot assign to '{}'", name
+                )),
+                None => return self.error(format!(
+                    "unknown variable '{}'", name
+                )),
+            };
+            self.pos += 1;
+            self.expression()?;
+            self.expect_sym(";")?;
+            self.emit(Op::Store(slot));
+        } else {
+            self.expression()?;
+            self.expect_sym(";")?;
+            self.emit(Op::Pop);
+        }
+        Ok(())
+    }
+
+    fn if_statement(&mut self) -> Result<(), ScriptError> {
+        self.expression()?;
+        let skip = self.emit(Op::JumpIfZero(0));
+        self.block()?;
+        if self.peek_keyword("else") {
+            self.pos += 1;
+            let end = self.emit(Op::Jump(0));
+            self.patch(skip);
+            if self.peek_keyword("if") {
+                self.pos += 1;
+                self.nest()?;
+                self.if_statement()?;
+                self.depth -= 1;
+            } else {
+                self.block()?;
+            }
+            self.patch(end);
+        } else {
+            self.patch(skip);
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<(), ScriptError> {
+        self.nest()?;
+        self.or()?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    // Short-circuit logic leaves exactly 0 or 1 on the stack.
+    fn or(&mut self) -> Result<(), ScriptError> {
+        self.and()?;
+        while self.eat_sym("||") {
+            let rhs = self.emit(Op::JumpIfZero(0));
+            self.emit(Op::Push(1));
+            let done = self.emit(Op::Jump(0));
+            self.patch(rhs);
+            self.and()?;
+            self.emit(Op::Not);
+            self.emit(Op::Not);
+            self.patch(done);
+        }
+        Ok(())
+    }
+
+    fn and(&mut self) -> Result<(), ScriptError> {
+        self.comparison()?;
+        while self.eat_sym("&&") {
+            let short = self.emit(Op::JumpIfZero(0));
+            self.comparison()?;
+            self.emit(Op::Not);
+            self.emit(Op::Not);
+            let done = self.emit(Op::Jump(0));
+            self.patch(short);
+            self.emit(Op::Push(0));
+            self.patch(done);
+        }
+        Ok(())
+    }
+
+    fn comparison(&mut self) -> Result<(), ScriptError> {
+        self.sum()?;
+        let op = match self.peek() {
+            Some(Tok::Sym("==")) => Op::Eq,
+            Some(Tok::Sym("!=")) => Op::Ne,
+            Some(Tok::Sym("<")) => Op::Lt,
+            Some(Tok::Sym("<=")) => Op::Le,
+            Some(Tok::Sym(">")) => Op::Gt,
+            Some(Tok::Sym(">=")) => Op::Ge,
+            _ => return Ok(()),
+        };
+        self.pos += 1;
+        self.sum()?;
+        self.emit(op);
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<(), ScriptError> {
+        self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Tok::Sym("+")) => Op::Add,
+                Some(Tok::Sym("-")) => Op::Sub,
+                _ => return Ok(()),
+            };
+            self.pos += 1;
+            self.product()?;
+            self.emit(op);
+        }
+    }
+
+    fn product(&mut self) -> Result<(), ScriptError> {
+        self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Tok::Sym("*")) => Op::Mul,
+                Some(Tok::Sym("/")) => Op::Div,
+                Some(Tok::Sym("%")) => Op::Rem,
+                _ => return Ok(()),
+            };
+            self.pos += 1;
+            self.unary()?;
+            self.emit(op);
+        }
+    }
+
+    fn unary(&mut self) -> Result<(), ScriptError> {
+        let op = if self.eat_sym("-") {
+            Op::Neg
+        } else if self.eat_sym("!") {
+            Op::Not
+        } else {
+            return self.primary();
+        };
+        self.nest()?;
+        self.unary()?;
+        self.depth -= 1;
+        self.emit(op);
+        Ok(())
+    }
+
+    fn primary(&mut self) -> Result<(), ScriptError> {
+        if let Some(Tok::Num(value)) = self.peek() {
+            let value = *value;
+            self.pos += 1;
+            self.emit(Op::Push(value));
+            return Ok(());
+        }
+        if self.eat_sym("(") {
+            self.expression()?;
+            return self.expect_sym(")");
+        }
+
+        let name = self.name()?;
+        if !self.eat_sym("(") {
+            return match self.lookup(&name) {
+                Some(slot) => {
+                    self.emit(Op::Load(slot));
+                    Ok(())
+                }
+                None => self.error(format!("unknown variable '{}'", name)),
+            };
+        }
+
+        let (builtin, arity) = match BUILTINS.iter()
+            .find(|(n, _, _)| *n == name)
+        {
+            Some(&(_, builtin, arity)) => (builtin, arity),
+            None => return self.error(format!("unknown function '{}'", name)),
+        };
+        let mut argc = 0;
+        if !self.eat_sym(")") {
+            loop {
+                self.expression()?;
+                argc += 1;
+                if self.eat_sym(")") {
+                    break;
+                }
+                self.expect_sym(",")?;
+            }
+        }
+        if argc != arity {
+            return self.error(format!(
+                "{} takes {} arguments, found {}", name, arity, argc
+            ));
+        }
+        self.emit(Op::Call(builtin));
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::io::{self, Write};
+
+use crate::ai::{Controller, Greedy};
+use crate::{CardID, Event, FeatureID, Game, Player, Rng, Rules};
+
+// Builds a controller for one character from the seed of a game.
+pub type MakeController = Box::<dyn Fn(u64) -> Box::<dyn Controller>>;
+
+// A batch of encounters between the same players. Each game gets its own
+// seed drawn from `seed`, and character `i` is driven by the controller
+// that `controllers[i]` builds from that game's seed; characters past
+// the end of `controllers` play greedily. Games still running after
+// `max_rounds` rounds are stopped and counted as unfinished.
+pub struct Simulation {
+    pub players: Vec::<Player>,
+    pub features: Vec::<FeatureID>,
+    pub controllers: Vec::<MakeController>,
+    pub games: usize,
+    pub seed: u64,
+    pub max_rounds: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub games: usize,
+    // Wins and win rate for each side with characters, by side index.
+    pub wins: Vec::<usize>,
+    pub win_rates: Vec::<f64>,
+    pub draws: usize,
+    pub unfinished: usize,
+    pub average_rounds: f64,
+    // Games an end condition awarded to a side nobody plays on.
+    #[serde(default)]
+    pub other_wins: usize,
+    pub cards: Vec::<CardStats>,
+}
+
+// `games` counts the games in which some character played the card, and
+// `win_rate` is how often the side that played it won those games.
+// `correlation` is the phi coefficient, over every side of every game,
+// between playing the card at least once and winning.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CardStats {
+    pub id: CardID,
+    pub name: String,
+    pub plays: u64,
+    pub games: u64,
+    pub win_rate: f64,
+    pub correlation: f64,
+}
+
+#[derive(Default)]
+struct Tally {
+    plays: u64,
+    // Sides that played the card and how many of them won.
+    played: u64,
+    played_won: u64,
+}
+
+impl Simulation {
+    pub fn new(players: Vec::<Player>, games: usize, seed: u64) -> Self {
+        Simulation {
+            players,
+            features: Vec::new(),
+            controllers: Vec::new(),
+            games,
+            seed,
+            max_rounds: 100,
+        }
+    }
+
+    fn play(&self, rules: &Rules, seed: u64) -> Game {
+        let mut game = Game::new(
+            self.players.clone(),
+            self.features.clone(),
+            rules,
+            seed
+        );
+        for cid in 0..self.players.len() {
+            let controller = match self.controllers.get(cid) {
+                Some(make) => make(seed.wrapping_add(cid as u64)),
+                None => Box::new(Greedy::new()),
+            };
+            game.set_controller(cid, controller);
+        }
+        game.run_controllers_until(self.max_rounds);
+        game
+    }
+
+    pub fn run(&self, rules: &Rules) -> Report {
+        let sides = self.players.iter()
+            .map(|player| player.character.side + 1)
+            .max()
+            .unwrap_or(0);
+        let mut wins = vec![0; sides];
+        let mut draws = 0;
+        let mut unfinished = 0;
+        let mut other_wins = 0;
+        let mut rounds = 0;
+        let mut won_sides: u64 = 0;
+        let mut tallies: Vec::<Tally> = rules.card_list.iter()
+            .map(|_| Tally::default())
+            .collect();
+
+        let mut rng = Rng::new(self.seed);
+        for _ in 0..self.games {
+            let game = self.play(rules, rng.next_u64());
+            let encounter = &game.encounter;
+            let winner = match &encounter.outcome {
+                Some(outcome) => outcome.winner,
+                None => None,
+            };
+            match (&encounter.outcome, winner) {
+                (None, _) => unfinished += 1,
+                (Some(_), None) => draws += 1,
+                // End conditions may name a side nobody plays on, which
+                // is no observation for the card correlations.
+                (Some(_), Some(side)) if side >= sides => other_wins += 1,
+                (Some(_), Some(side)) => {
+                    won_sides += 1;
+                    wins[side] += 1;
+                }
+            }
+            rounds += encounter.round.min(self.max_rounds);
+
+            let mut played = vec![vec![false; sides]; tallies.len()];
+            for event in encounter.events() {
+                if let Event::CardPlayed { character, kind, .. } = *event {
+                    if let Some(tally) = tallies.get_mut(kind as usize) {
+                        tally.plays += 1;
+                        let side = encounter.characters[character].side;
+                        played[kind as usize][side] = true;
+                    }
+                }
+            }
+            for (tally, played) in tallies.iter_mut().zip(played) {
+                for (side, played) in played.into_iter().enumerate() {
+                    if played {
+                        tally.played += 1;
+                        if winner == Some(side) {
+                            tally.played_won += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let rate = |n: usize| if self.games == 0 {
+            0.0
+        } else {
+            n as f64 / self.games as f64
+        };
+        let observations = (self.games * sides) as u64;
+        let cards = tallies.iter().enumerate().map(|(id, tally)| CardStats {
+            id: id as CardID,
+            name: rules.card_list[id].name.clone(),
+            plays: tally.plays,
+            games: tally.played,
+            win_rate: if tally.played == 0 {
+                0.0
+            } else {
+                tally.played_won as f64 / tally.played as f64
+            },
+            correlation: phi(
+                observations,
+                tally.played,
+                won_sides,
+                tally.played_won
+            ),
+        }).collect();
+
+        Report {
+            games: self.games,
+            win_rates: wins.iter().map(|&n| rate(n)).collect(),
+            wins,
+            draws,
+            unfinished,
+            other_wins,
+            average_rounds: if self.games == 0 {
+                0.0
+            } else {
+                rounds as f64 / self.games as f64
+            },
+            cards,
+        }
+    }
+}
+
+// The correlation between two yes/no variables over `n` observations,
+// given how often each was true and how often both were.
+fn phi(n: u64, x: u64, y: u64, xy: u64) -> f64 {
+    let (n, x, y, xy) = (n as f64, x as f64, y as f64, xy as f64);
+    let spread = ((n * x - x * x) * (n * y - y * y)).sqrt();
+    if spread == 0.0 {
+        0.0
+    } else {
+        (n * xy - x * y) / spread
+    }
+}
+
+impl Report {
+    // Writes the report as three CSV tables separated by blank lines:
+    // results by side, overall figures, and per-card statistics.
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "side,wins,win_rate")?;
+        for (side, (wins, rate)) in self.wins.iter()
+            .zip(self.win_rates.iter())
+            .enumerate()
+        {
+            writeln!(out, "{},{},{}", side, wins, rate)?;
+        }
+        writeln!(out)?;
+        writeln!(out, "games,draws,unfinished,average_rounds,other_wins")?;
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            self.games,
+            self.draws,
+            self.unfinished,
+            self.average_rounds,
+            self.other_wins
+        )?;
+        writeln!(out)?;
+        writeln!(out, "card,name,plays,games,win_rate,correlation")?;
+        for card in self.cards.iter() {
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                card.id,
+                csv_field(&card.name),
+                card.plays,
+                card.games,
+                card.win_rate,
+                card.correlation
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::undo::History;
+use crate::{
+    CardID, Character, CharacterIdx, Encounter, Event, FeatureID,
+    FeatureIdx, Game, Outcome, Phase, PlayerID, Recording, Rng, Rules,
+    StackEntry, UndoMode, Window, SCRIPT_STEP_LIMIT, TRIGGER_LIMIT,
+};
+
+// Bump this whenever the layout below changes. Fields added later must
+// carry `#[serde(default)]` so that older saves still deserialize, and
+// anything else an older version wrote is upgraded as it is read:
+// version 1 saves end with a bare `EncounterEnded`, which loads as one
+// without an outcome.
+pub const SNAPSHOT_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub players: Vec::<(PlayerID, CharacterIdx)>,
+    pub encounter: EncounterSnapshot,
+    pub recording: Recording,
+}
+
+// Everything in an `Encounter` except the shared `Rules`, whose cards,
+// features, traits and statuses are referenced by ID and re-linked on
+// load. End conditions also come from the `Rules` given to
+// `Game::from_snapshot`.
+#[derive(Serialize, Deserialize)]
+pub struct EncounterSnapshot {
+    pub characters: Vec::<Character>,
+    pub features: Vec::<FeatureID>,
+    pub done: bool,
+    pub active: CharacterIdx,
+    pub phase: Phase,
+    pub round: u64,
+    pub draw_count: usize,
+    pub rng: Rng,
+    #[serde(deserialize_with = "saved_events")]
+    pub events: Vec::<Event>,
+    #[serde(default)]
+    pub outcome: Option<Outcome>,
+    #[serde(default)]
+    pub destroyed: Vec::<FeatureIdx>,
+    #[serde(default)]
+    pub conceded: Vec::<CharacterIdx>,
+    #[serde(default)]
+    pub stack: Vec::<StackEntry>,
+    #[serde(default)]
+    pub window: Option<Window>,
+    #[serde(default = "script_step_limit")]
+    pub script_step_limit: u64,
+    #[serde(default = "trigger_limit")]
+    pub trigger_limit: usize,
+}
+
+fn script_step_limit() -> u64 {
+    SCRIPT_STEP_LIMIT
+}
+
+fn trigger_limit() -> usize {
+    TRIGGER_LIMIT
+}
+
+// An event as saved by any snapshot version. Telling them apart needs a
+// self-describing format such as JSON.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedEvent {
+    Current(Event),
+    Version1(Version1Event),
+}
+
+#[derive(Deserialize)]
+enum Version1Event {
+    EncounterEnded,
+}
+
+fn saved_events<'de, D: Deserializer<'de>>(
+    deserializer: D
+) -> Result<Vec::<Event>, D::Error> {
+    let saved = Vec::<SavedEvent>::deserialize(deserializer)?;
+    Ok(saved.into_iter().map(|event| match event {
+        SavedEvent::Current(event) => event,
+        SavedEvent::Version1(Version1Event::EncounterEnded) => {
+            Event::EncounterEnded { outcome: None }
+        }
+    }).collect())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    UnsupportedVersion(u32),
+    UnknownCard {
+        character: CharacterIdx,
+        card: CardID,
+    },
+    UnknownFeature {
+        feature: FeatureIdx,
+        id: FeatureID,
+    },
+    // An index into the encounter's characters, as the active character,
+    // a player's character, or in the stack or response window.
+    NoSuchCharacter(CharacterIdx),
+    // An index into the encounter's features.
+    NoSuchFeature(FeatureIdx),
+    // A card in a hand, draw pile, discard pile or on the stack whose slot
+    // is not in its character's card list.
+    NoSuchSlot {
+        character: CharacterIdx,
+        slot: CardID,
+    },
+    // A response window with nobody left to respond.
+    EmptyWindow,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion(v) => write!(
+                f, "unsupported snapshot version {}", v
+            ),
+            SnapshotError::UnknownCard { character, card } => write!(
+                f, "character {} references unknown card {}",
+                character, card
+            ),
+            SnapshotError::UnknownFeature { feature, id } => write!(
+                f, "feature {} references unknown feature {}",
+                feature, id
+            ),
+            SnapshotError::NoSuchCharacter(cid) => write!(
+                f, "no character {}", cid
+            ),
+            SnapshotError::NoSuchFeature(fid) => write!(
+                f, "no feature {}", fid
+            ),
+            SnapshotError::NoSuchSlot { character, slot } => write!(
+                f, "character {} has no card in slot {}", character, slot
+            ),
+            SnapshotError::EmptyWindow => write!(
+                f, "response window with nobody waiting"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Game {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut players: Vec::<_> = self.players.iter()
+            .map(|(&id, &cid)| (id, cid))
+            .collect();
+        players.sort();
+
+        let encounter = &self.encounter;
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            players,
+            encounter: EncounterSnapshot {
+                characters: encounter.characters.clone(),
+                features: encounter.features.clone(),
+                done: encounter.done,
+                active: encounter.active,
+                phase: encounter.phase,
+                round: encounter.round,
+                draw_count: encounter.draw_count,
+                rng: encounter.rng.clone(),
+                events: encounter.events.clone(),
+                outcome: encounter.outcome.clone(),
+                destroyed: encounter.destroyed.clone(),
+                conceded: encounter.conceded.clone(),
+                stack: encounter.stack.clone(),
+                window: encounter.window.clone(),
+                script_step_limit: encounter.script_step_limit,
+                trigger_limit: encounter.trigger_limit,
+            },
+            recording: self.recording.clone(),
+        }
+    }
+
+    pub fn from_snapshot(
+        snapshot: Snapshot,
+        rules: &Rules
+    ) -> Result<Game, SnapshotError> {
+        if snapshot.version == 0 || snapshot.version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+
+        let saved = snapshot.encounter;
+        saved.check_indices(&snapshot.players)?;
+        for (cid, character) in saved.characters.iter().enumerate() {
+            for &card in character.deck.clist.iter().flatten() {
+                if card as usize >= rules.card_list.len() {
+                    return Err(SnapshotError::UnknownCard {
+                        character: cid,
+                        card,
+                    });
+                }
+            }
+        }
+        for (fid, &id) in saved.features.iter().enumerate() {
+            if id as usize >= rules.feature_list.len() {
+                return Err(SnapshotError::UnknownFeature {
+                    feature: fid,
+                    id,
+                });
+            }
+        }
+        for entry in saved.stack.iter() {
+            match *entry {
+                StackEntry::Card { character, kind, .. }
+                    if kind as usize >= rules.card_list.len() =>
+                {
+                    return Err(SnapshotError::UnknownCard {
+                        character,
+                        card: kind,
+                    });
+                }
+                StackEntry::Feature { feature, id, .. }
+                    if id as usize >= rules.feature_list.len() =>
+                {
+                    return Err(SnapshotError::UnknownFeature { feature, id });
+                }
+                _ => {}
+            }
+        }
+
+        let mut encounter = Encounter::new(
+            saved.characters,
+            saved.features,
+            rules,
+            0
+        );
+        encounter.done = saved.done;
+        encounter.active = saved.active;
+        encounter.phase = saved.phase;
+        encounter.round = saved.round;
+        encounter.draw_count = saved.draw_count;
+        encounter.rng = saved.rng;
+        encounter.events = saved.events;
+        encounter.dispatched = encounter.events.len();
+        encounter.outcome = saved.outcome;
+        encounter.destroyed = saved.destroyed;
+        encounter.conceded = saved.conceded;
+        encounter.stack = saved.stack;
+        encounter.window = saved.window;
+        encounter.script_step_limit = saved.script_step_limit;
+        encounter.trigger_limit = saved.trigger_limit;
+
+        Ok(Game {
+            players: snapshot.players.into_iter().collect::<HashMap<_, _>>(),
+            encounter,
+            recording: snapshot.recording,
+            history: History::new(UndoMode::Disabled),
+            controllers: HashMap::new(),
+        })
+    }
+}
+
+impl EncounterSnapshot {
+    // Checks every character, feature and card slot index in the saved
+    // state, so that a corrupt save is refused instead of panicking
+    // later.
+    fn check_indices(
+        &self,
+        players: &[(PlayerID, CharacterIdx)]
+    ) -> Result<(), SnapshotError> {
+        let character = |cid: CharacterIdx| {
+            if cid < self.characters.len() {
+                Ok(())
+            } else {
+                Err(SnapshotError::NoSuchCharacter(cid))
+            }
+        };
+        let feature = |fid: FeatureIdx| {
+            if fid < self.features.len() {
+                Ok(())
+            } else {
+                Err(SnapshotError::NoSuchFeature(fid))
+            }
+        };
+        let slot = |cid: CharacterIdx, slot: CardID| {
+            if (slot as usize) < self.characters[cid].deck.clist.len() {
+                Ok(())
+            } else {
+                Err(SnapshotError::NoSuchSlot { character: cid, slot })
+            }
+        };
+
+        character(self.active)?;
+        for &(_, cid) in players.iter() {
+            character(cid)?;
+        }
+        for (cid, ch) in self.characters.iter().enumerate() {
+            let deck = &ch.deck;
+            for &id in deck.deck.iter().chain(&deck.hand).chain(&deck.discard) {
+                slot(cid, id)?;
+            }
+        }
+        for &fid in self.destroyed.iter() {
+            feature(fid)?;
+        }
+        for &cid in self.conceded.iter() {
+            character(cid)?;
+        }
+        for entry in self.stack.iter() {
+            match entry {
+                StackEntry::Card {
+                    character: cid, targets, feature: fid, card, ..
+                } => {
+                    character(*cid)?;
+                    slot(*cid, *card)?;
+                    for &target in targets.iter() {
+                        character(target)?;
+                    }
+                    if let Some(fid) = *fid {
+                        feature(fid)?;
+                    }
+                }
+                StackEntry::Feature { character: cid, feature: fid, .. } => {
+                    character(*cid)?;
+                    feature(*fid)?;
+                }
+            }
+        }
+        if let Some(window) = &self.window {
+            if window.waiting.is_empty() {
+                return Err(SnapshotError::EmptyWindow);
+            }
+            for &cid in window.waiting.iter() {
+                character(cid)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use crate::{
+    ActionError, CardID, CharacterIdx, Encounter, Event, FeatureID,
+    FeatureIdx, Phase, Targets,
+};
+
+// Cards and reaction features waiting to resolve. Each entry is paid
+// for when it goes on the stack, and resolves later with the targets
+// chosen then.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StackEntry {
+    Card {
+        character: CharacterIdx,
+        targets: Vec::<CharacterIdx>,
+        // The feature a `Targets::Feature` card was played on.
+        #[serde(default)]
+        feature: Option<FeatureIdx>,
+        card: CardID,
+        kind: CardID,
+    },
+    Feature {
+        character: CharacterIdx,
+        feature: FeatureIdx,
+        id: FeatureID,
+    },
+}
+
+// Characters yet to respond to the top of the stack, in priority order.
+// Only the first of them may respond or pass.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    pub waiting: Vec::<CharacterIdx>,
+}
+
+impl Encounter {
+    pub fn stack(&self) -> &[StackEntry] {
+        &self.stack
+    }
+
+    pub fn window(&self) -> Option<&Window> {
+        self.window.as_ref()
+    }
+
+    // The character expected to act next: the one holding priority in
+    // an open response window, or else the active character.
+    pub fn actor(&self) -> CharacterIdx {
+        match &self.window {
+            Some(window) => window.waiting[0],
+            None => self.active,
+        }
+    }
+
+    // Puts an entry on the stack and offers a response window to every
+    // other character able to respond, in turn order after `pusher`.
+    // With nobody to ask the stack resolves at once.
+    pub(crate) fn push_entry(
+        &mut self,
+        pusher: CharacterIdx,
+        entry: StackEntry
+    ) {
+        self.stack.push(entry);
+        let count = self.characters.len();
+        let waiting: Vec::<CharacterIdx> = (1..count)
+            .map(|offset| (pusher + offset) % count)
+            .filter(|&cid| self.can_respond(cid))
+            .collect();
+        if waiting.is_empty() {
+            self.window = None;
+            self.resolve_stack();
+        } else {
+            self.emit(Event::ResponseWindow {
+                waiting: waiting.clone(),
+            });
+            self.window = Some(Window { waiting });
+        }
+    }
+
+    fn can_respond(&self, cid: CharacterIdx) -> bool {
+        if self.has_conceded(cid) {
+            return false;
+        }
+        let reaction_feature = (0..self.features.len())
+            .any(|fid| self.check_reaction(cid, fid).is_ok());
+        if reaction_feature {
+            return true;
+        }
+
+        let deck = &self.characters[cid].deck;
+        (0..deck.hand.len()).any(|i| {
+            let cd = match self.hand_card(cid, i) {
+                Some(cd) => cd,
+                None => return false,
+            };
+            cd.reaction
+                && self.check_cost(cid, &cd.cost).is_ok()
+                && (matches!(cd.targets, Targets::None | Targets::AllEnemies)
+                    || !self.legal_targets(cid, i).is_empty())
+        })
+    }
+
+    fn check_priority(&self, cid: CharacterIdx) -> Result<(), ActionError> {
+        if self.done {
+            return Err(ActionError::EncounterOver);
+        }
+        let window = self.window.as_ref().ok_or(ActionError::NoWindow)?;
+        if window.waiting[0] != cid {
+            return Err(ActionError::NoPriority {
+                character: cid,
+                priority: window.waiting[0],
+            });
+        }
+        Ok(())
+    }
+
+    // Plays reaction card `i` from the hand of the character holding
+    // priority on top of the stack.
+    pub fn respond(
+        &mut self,
+        cid: CharacterIdx,
+        tid: CharacterIdx,
+        i: usize
+    ) -> Result<(), ActionError> {
+        self.check_priority(cid)?;
+        let (slot, kind, targets, feature) =
+            self.prepare_card(cid, tid, i, true)?;
+        self.pay_card(cid, i, slot, kind, &targets, feature);
+        self.push_entry(cid, StackEntry::Card {
+            character: cid,
+            targets,
+            feature,
+            card: slot,
+            kind,
+        });
+        self.dispatch();
+        self.check_end();
+        Ok(())
+    }
+
+    // Checks that reaction feature `fid` may answer for `cid`: it must
+    // react for the character's side and, so that a window cannot be
+    // reopened forever, not be on the stack already.
+    pub(crate) fn check_reaction(
+        &self,
+        cid: CharacterIdx,
+        fid: FeatureIdx
+    ) -> Result<FeatureID, ActionError> {
+        let &id = self.features.get(fid)
+            .ok_or(ActionError::NoSuchFeature(fid))?;
+        let feature = self.feature_list.get(id as usize)
+            .ok_or(ActionError::UnknownFeature(id))?;
+        let side = self.characters.get(cid)
+            .ok_or(ActionError::NoSuchCharacter(cid))?
+            .side;
+        if self.is_destroyed(fid) {
+            return Err(ActionError::FeatureDestroyed(fid));
+        }
+        if !feature.reaction {
+            return Err(ActionError::NotReaction);
+        }
+        if feature.side.is_some_and(|s| s != side) {
+            return Err(ActionError::NotYourReaction(fid));
+        }
+        let pending = self.stack.iter().any(|entry| matches!(
+            *entry,
+            StackEntry::Feature { feature, .. } if feature == fid
+        ));
+        if pending {
+            return Err(ActionError::ReactionPending(fid));
+        }
+        Ok(id)
+    }
+
+    // Activates a reaction feature on behalf of the character holding
+    // priority, with that character as player and target.
+    pub fn respond_feature(
+        &mut self,
+        cid: CharacterIdx,
+        fid: FeatureIdx
+    ) -> Result<(), ActionError> {
+        self.check_priority(cid)?;
+        let id = self.check_reaction(cid, fid)?;
+
+        self.emit(Event::FeatureActivated { feature: fid, id });
+        self.push_entry(cid, StackEntry::Feature {
+            character: cid,
+            feature: fid,
+            id,
+        });
+        self.dispatch();
+        self.check_end();
+        Ok(())
+    }
+
+    // Declines to respond. Once everyone has passed the whole stack
+    // resolves, last in first out.
+    pub fn pass(&mut self, cid: CharacterIdx) -> Result<(), ActionError> {
+        self.check_priority(cid)?;
+        self.emit(Event::Passed { character: cid });
+        let done = match self.window.as_mut() {
+            Some(window) => {
+                window.waiting.remove(0);
+                window.waiting.is_empty()
+            }
+            None => true,
+        };
+        if done {
+            self.window = None;
+            self.resolve_stack();
+        }
+        self.dispatch();
+        self.check_end();
+        Ok(())
+    }
+
+    // Removes the next entry due to resolve without resolving it. Called
+    // from a resolving entry, this cancels whatever it responded to.
+    pub fn counter(&mut self) -> bool {
+        let entry = match self.stack.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let character = match entry {
+            StackEntry::Card { character, .. }
+            | StackEntry::Feature { character, .. } => character,
+        };
+        self.emit(Event::Countered { character });
+        true
+    }
+
+    pub(crate) fn resolve_stack(&mut self) {
+        while let Some(entry) = self.stack.pop() {
+            match entry {
+                StackEntry::Card {
+                    character, targets, feature, kind, ..
+                } => {
+                    let card_list = Arc::clone(&self.card_list);
+                    let cd = &card_list[kind as usize];
+                    for target in targets {
+                        (cd.effect)(self, character, feature.unwrap_or(target));
+                        for effect in cd.effects.iter() {
+                            effect.resolve(self, character, target, feature);
+                        }
+                    }
+                }
+                StackEntry::Feature { character, feature, id } => {
+                    let feature_list = Arc::clone(&self.feature_list);
+                    let fd = &feature_list[id as usize];
+                    (fd.effect)(self, feature);
+                    for effect in fd.effects.iter() {
+                        effect.resolve(self, character, character, Some(feature));
+                    }
+                }
+            }
+        }
+        // A character that conceded while its own play awaited responses
+        // hands on its turn once the stack is clear.
+        if self.has_conceded(self.active) && self.phase == Phase::Main {
+            self.finish_turn();
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{CharacterIdx, Encounter, Event, TraitID, TraitValue};
+
+pub type StatusID = u64;
+
+// A buff or debuff such as poison, shield, stun or strength-up. Each
+// stack adds `modifiers` to the effective trait values of the character
+// it is attached to, and `on_tick` runs at the start of that character's
+// turns. Characters holding a `skip_turn` status lose their turns.
+pub struct Status {
+    pub name: String,
+    pub description: String,
+    pub modifiers: Vec::<(TraitID, TraitValue)>,
+    pub on_tick: Option<fn(
+        &mut Encounter, character: CharacterIdx, stacks: u32
+    )>,
+    pub skip_turn: bool,
+}
+
+impl Status {
+    pub fn new(name: &str, description: &str) -> Self {
+        Status {
+            name: name.to_string(),
+            description: description.to_string(),
+            modifiers: Vec::new(),
+            on_tick: None,
+            skip_turn: false,
+        }
+    }
+}
+
+// A status attached to a character. `turns` counts the character's
+// remaining turns, decremented as each of them ends; `None` lasts until
+// the status is removed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub id: StatusID,
+    pub stacks: u32,
+    pub turns: Option<u32>,
+}
+
+impl Encounter {
+    pub fn statuses(&self, cid: CharacterIdx) -> &[StatusEffect] {
+        self.characters.get(cid).map_or(&[], |ch| &ch.statuses)
+    }
+
+    pub fn status_stacks(&self, cid: CharacterIdx, id: StatusID) -> u32 {
+        self.statuses(cid).iter()
+            .find(|s| s.id == id)
+            .map_or(0, |s| s.stacks)
+    }
+
+    pub(crate) fn status_modifier(
+        &self,
+        cid: CharacterIdx,
+        id: TraitID
+    ) -> TraitValue {
+        let mut total: TraitValue = 0;
+        for status in self.statuses(cid) {
+            if let Some(def) = self.status_list.get(status.id as usize) {
+                for &(tid, by) in def.modifiers.iter() {
+                    if tid == id {
+                        total = total.saturating_add(
+                            by.saturating_mul(status.stacks as TraitValue)
+                        );
+                    }
+                }
+            }
+        }
+        total
+    }
+
+    // Adds stacks of a status, merging with an existing one of the same
+    // kind by keeping the longer duration.
+    pub fn apply_status(
+        &mut self,
+        cid: CharacterIdx,
+        id: StatusID,
+        stacks: u32,
+        turns: Option<u32>
+    ) -> bool {
+        if cid >= self.characters.len() || stacks == 0 {
+            return false;
+        }
+
+        let before = self.watch_traits(cid);
+        let statuses = &mut self.characters[cid].statuses;
+        let status = match statuses.iter().position(|s| s.id == id) {
+            Some(i) => {
+                let status = &mut statuses[i];
+                status.stacks = status.stacks.saturating_add(stacks);
+                status.turns = match (status.turns, turns) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                };
+                status.clone()
+            }
+            None => {
+                let status = StatusEffect { id, stacks, turns };
+                statuses.push(status.clone());
+                status
+            }
+        };
+        self.emit(Event::StatusApplied {
+            character: cid,
+            id,
+            stacks: status.stacks,
+            turns: status.turns,
+        });
+        self.notify_traits(cid, before);
+        true
+    }
+
+    pub fn remove_status(&mut self, cid: CharacterIdx, id: StatusID) -> bool {
+        let i = match self.statuses(cid).iter().position(|s| s.id == id) {
+            Some(i) => i,
+            None => return false,
+        };
+
+        let before = self.watch_traits(cid);
+        self.characters[cid].statuses.remove(i);
+        self.emit(Event::StatusRemoved { character: cid, id });
+        self.notify_traits(cid, before);
+        true
+    }
+
+    pub fn is_stunned(&self, cid: CharacterIdx) -> bool {
+        self.statuses(cid).iter().any(|s| {
+            self.status_list.get(s.id as usize).is_some_and(|d| d.skip_turn)
+        })
+    }
+
+    pub(crate) fn tick_statuses(&mut self, cid: CharacterIdx) {
+        let ticking: Vec::<_> = self.statuses(cid).iter()
+            .map(|s| (s.id, s.stacks))
+            .collect();
+        for (id, stacks) in ticking {
+            let on_tick = self.status_list.get(id as usize)
+                .and_then(|def| def.on_tick);
+            if let Some(on_tick) = on_tick {
+                on_tick(self, cid, stacks);
+            }
+        }
+    }
+
+    pub(crate) fn expire_statuses(&mut self, cid: CharacterIdx) {
+        let mut expired = Vec::new();
+        if let Some(character) = self.characters.get_mut(cid) {
+            for status in character.statuses.iter_mut() {
+                if let Some(turns) = status.turns.as_mut() {
+                    *turns = turns.saturating_sub(1);
+                    if *turns == 0 {
+                        expired.push(status.id);
+                    }
+                }
+            }
+        }
+        for id in expired {
+            self.remove_status(cid, id);
+        }
+    }
+}
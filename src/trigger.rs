@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use crate::{
+    CardID, CharacterIdx, EndCondition, Encounter, Event, FeatureIdx,
+    TraitID, TraitValue,
+};
+
+// Engine events a feature or card can subscribe to. The character an
+// event is about becomes the target of the triggered effects: the
+// character whose turn starts or ends, the one who played a card, or
+// the one whose trait changed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Trigger {
+    TurnStarted,
+    TurnEnded,
+    CardPlayed,
+    // Trait `id` dropped from at least `threshold` to below it.
+    TraitBelow {
+        id: TraitID,
+        threshold: TraitValue,
+    },
+    // A trait named by a `Defeat` end condition dropped to zero or below.
+    Defeated,
+}
+
+enum Subscriber {
+    Feature(FeatureIdx),
+    Card {
+        holder: CharacterIdx,
+        card: CardID,
+        kind: CardID,
+    },
+}
+
+impl Trigger {
+    // The character `event` is about, if it sets off this trigger.
+    fn matches(
+        &self,
+        event: &Event,
+        end_conditions: &[EndCondition]
+    ) -> Option<CharacterIdx> {
+        let crossed = |old: Option<TraitValue>, new, threshold| {
+            new < threshold && old.is_none_or(|old| old >= threshold)
+        };
+        match (*self, event) {
+            (Trigger::TurnStarted, Event::TurnStarted { character, .. })
+            | (Trigger::TurnEnded, Event::TurnEnded { character })
+            | (Trigger::CardPlayed, Event::CardPlayed { character, .. }) =>
+                Some(*character),
+            (
+                Trigger::TraitBelow { id, threshold },
+                Event::TraitChanged { character, id: changed, old, new },
+            ) if id == *changed && crossed(*old, *new, threshold) =>
+                Some(*character),
+            (
+                Trigger::Defeated,
+                Event::TraitChanged { character, id: changed, old, new },
+            ) if crossed(*old, *new, 1) && end_conditions.iter().any(|c| {
+                matches!(c, EndCondition::Defeat { id } if id == changed)
+            }) => Some(*character),
+            _ => None,
+        }
+    }
+}
+
+impl Encounter {
+    // Runs triggered features and cards for every event emitted since the
+    // last dispatch, including events the triggers themselves emit.
+    // Features fire first in index order, then cards in the hands of
+    // each character in turn. Once `trigger_limit` effects have fired in
+    // one dispatch the rest are dropped, which ends trigger loops.
+    pub(crate) fn dispatch(&mut self) {
+        let mut fired = 0;
+        while self.dispatched < self.events.len() {
+            let event = self.events[self.dispatched].clone();
+            self.dispatched += 1;
+
+            for (subscriber, subject) in self.subscribers(&event) {
+                if fired >= self.trigger_limit {
+                    self.emit(Event::TriggerLimitReached);
+                    self.dispatched = self.events.len();
+                    return;
+                }
+                fired += 1;
+                self.fire(subscriber, subject);
+            }
+        }
+    }
+
+    fn subscribers(&self, event: &Event) -> Vec::<(Subscriber, CharacterIdx)> {
+        let mut found = Vec::new();
+        let ends = &self.end_conditions;
+        for (fid, &id) in self.features.iter().enumerate() {
+            if self.is_destroyed(fid) {
+                continue;
+            }
+            if let Some(feature) = self.feature_list.get(id as usize) {
+                for trigger in feature.triggers.iter() {
+                    if let Some(subject) = trigger.matches(event, ends) {
+                        found.push((Subscriber::Feature(fid), subject));
+                    }
+                }
+            }
+        }
+        for (holder, character) in self.characters.iter().enumerate() {
+            let deck = &character.deck;
+            for &card in deck.hand.iter() {
+                let kind = match deck.clist.get(card as usize) {
+                    Some(&Some(kind)) => kind,
+                    _ => continue,
+                };
+                if let Some(cd) = self.card_list.get(kind as usize) {
+                    for trigger in cd.triggers.iter() {
+                        if let Some(subject) = trigger.matches(event, ends) {
+                            let subscriber = Subscriber::Card {
+                                holder,
+                                card,
+                                kind,
+                            };
+                            found.push((subscriber, subject));
+                        }
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn fire(&mut self, subscriber: Subscriber, subject: CharacterIdx) {
+        match subscriber {
+            Subscriber::Feature(fid) => {
+                let feature_list = Arc::clone(&self.feature_list);
+                let id = self.features[fid];
+                let feature = &feature_list[id as usize];
+                self.emit(Event::FeatureTriggered { feature: fid, id });
+                (feature.effect)(self, fid);
+                let active = self.active;
+                for effect in feature.effects.iter() {
+                    effect.resolve(self, active, subject, Some(fid));
+                }
+            }
+            Subscriber::Card { holder, card, kind } => {
+                let card_list = Arc::clone(&self.card_list);
+                let cd = &card_list[kind as usize];
+                self.emit(Event::CardTriggered {
+                    character: holder,
+                    card,
+                    kind,
+                });
+                (cd.effect)(self, holder, subject);
+                for effect in cd.effects.iter() {
+                    effect.apply(self, holder, subject);
+                }
+            }
+        }
+    }
+}
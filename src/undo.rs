@@ -0,0 +1,114 @@
+use crate::replay::Step;
+use crate::{Encounter, Event, Game};
+
+// `UntilRandom` allows undo only back to the last action that consumed
+// randomness, such as a draw from a shuffled deck, so that players
+// cannot take back a move after seeing what it revealed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UndoMode {
+    Disabled,
+    Enabled,
+    UntilRandom,
+}
+
+// Undo and redo stacks of whole encounter snapshots, each paired with
+// the length of the game's recording at that point.
+pub struct History {
+    pub mode: UndoMode,
+    pub limit: usize,
+    undo: Vec::<(Encounter, usize)>,
+    redo: Vec::<(Encounter, Vec::<Step>)>,
+}
+
+impl History {
+    pub fn new(mode: UndoMode) -> Self {
+        History {
+            mode,
+            limit: 64,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub(crate) fn before(
+        &self,
+        encounter: &Encounter,
+        steps: usize
+    ) -> Option<(Encounter, usize)> {
+        match self.mode {
+            UndoMode::Disabled => None,
+            _ => Some((encounter.clone(), steps)),
+        }
+    }
+
+    pub(crate) fn commit(
+        &mut self,
+        before: Option<(Encounter, usize)>,
+        after: &Encounter
+    ) {
+        let before = match before {
+            Some(before) => before,
+            None => return,
+        };
+        self.redo.clear();
+        if self.mode == UndoMode::UntilRandom && revealed(&before.0, after) {
+            self.undo.clear();
+            return;
+        }
+
+        self.undo.push(before);
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+    }
+}
+
+// Whether anything random came to light between the two states: the rng
+// was drawn on, or a card was drawn from a shuffled deck.
+fn revealed(before: &Encounter, after: &Encounter) -> bool {
+    let new = after.events().get(before.events().len()..).unwrap_or(&[]);
+    before.rng.state() != after.rng.state()
+        || new.iter().any(|event| matches!(
+            event,
+            Event::CardDrawn { .. } | Event::Reshuffled { .. }
+        ))
+}
+
+impl Game {
+    pub fn set_undo_mode(&mut self, mode: UndoMode) {
+        self.history = History {
+            limit: self.history.limit,
+            ..History::new(mode)
+        };
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let (encounter, steps) = match self.history.undo.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let after = std::mem::replace(&mut self.encounter, encounter);
+        let undone = self.recording.steps.split_off(steps);
+        self.history.redo.push((after, undone));
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let (encounter, redone) = match self.history.redo.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let before = std::mem::replace(&mut self.encounter, encounter);
+        self.history.undo.push((before, self.recording.steps.len()));
+        self.recording.steps.extend(redone);
+        true
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{
+    CardID, CharacterIdx, Event, FeatureID, Game, Outcome, Phase, PlayerID,
+    StackEntry, StatusEffect, TraitID, TraitValue, Window,
+};
+
+// What one player may know about a character. Cards are given by kind,
+// an index into `card_list`, and `hand` is only filled in for the
+// viewer's own character, in hand order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacterView {
+    pub side: usize,
+    // Effective values of every registered or stored trait, by id.
+    pub traits: Vec::<(TraitID, TraitValue)>,
+    pub statuses: Vec::<StatusEffect>,
+    pub hand: Option<Vec::<CardID>>,
+    pub hand_size: usize,
+    pub deck_size: usize,
+    pub discard: Vec::<CardID>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureView {
+    pub id: FeatureID,
+    pub destroyed: bool,
+}
+
+// The state of a game as sent to one player: no card order and no other
+// character's hand, which `Game::get_character` would reveal. `window`
+// only lists the viewer among those waiting to respond, since who else
+// may respond depends on their hands.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameView {
+    pub player: PlayerID,
+    pub character: Option<CharacterIdx>,
+    pub characters: Vec::<CharacterView>,
+    pub features: Vec::<FeatureView>,
+    pub active: CharacterIdx,
+    pub phase: Phase,
+    pub round: u64,
+    pub done: bool,
+    pub outcome: Option<Outcome>,
+    pub stack: Vec::<StackEntry>,
+    pub window: Option<Window>,
+}
+
+impl Game {
+    // The view of `player`, or of a spectator for an unknown id.
+    pub fn view(&self, player: PlayerID) -> GameView {
+        let own = self.players.get(&player).copied();
+        let encounter = &self.encounter;
+        let kinds = |cid: CharacterIdx, cards: &[CardID]| -> Vec::<CardID> {
+            let clist = &encounter.characters[cid].deck.clist;
+            cards.iter()
+                .filter_map(|&slot| clist.get(slot as usize).copied().flatten())
+                .collect()
+        };
+
+        let characters = encounter.characters.iter()
+            .enumerate()
+            .map(|(cid, character)| {
+                let mut ids: Vec::<TraitID> = (0..encounter.trait_list.len())
+                    .map(|id| id as TraitID)
+                    .chain(character.traits().keys().copied())
+                    .collect();
+                ids.sort();
+                ids.dedup();
+                let deck = &character.deck;
+                CharacterView {
+                    side: character.side,
+                    traits: ids.into_iter()
+                        .map(|id| (id, encounter.get_trait(cid, id)))
+                        .collect(),
+                    statuses: encounter.statuses(cid).to_vec(),
+                    hand: if own == Some(cid) {
+                        Some(kinds(cid, &deck.hand))
+                    } else {
+                        None
+                    },
+                    hand_size: deck.hand.len(),
+                    deck_size: deck.deck.len(),
+                    discard: kinds(cid, &deck.discard),
+                }
+            })
+            .collect();
+
+        GameView {
+            player,
+            character: own,
+            characters,
+            features: encounter.features.iter()
+                .enumerate()
+                .map(|(fid, &id)| FeatureView {
+                    id,
+                    destroyed: encounter.is_destroyed(fid),
+                })
+                .collect(),
+            active: encounter.active,
+            phase: encounter.phase,
+            round: encounter.round,
+            done: encounter.done,
+            outcome: encounter.outcome.clone(),
+            stack: encounter.stack().to_vec(),
+            window: encounter.window().map(|window| Window {
+                waiting: redact_waiting(&window.waiting, own),
+            }),
+        }
+    }
+
+    // Events from index `from` on that `player` may see. Other
+    // characters' draws and hand card triggers are left out, since they
+    // name cards in hand, and so are their passes and places in response
+    // windows, which tell whether they hold a reaction.
+    pub fn view_events(&self, player: PlayerID, from: usize) -> Vec::<Event> {
+        let own = self.players.get(&player).copied();
+        let events = self.encounter.events();
+        events.get(from..).unwrap_or(&[]).iter()
+            .filter_map(|event| match event {
+                Event::CardDrawn { character, .. }
+                | Event::CardTriggered { character, .. }
+                | Event::Passed { character }
+                    if own != Some(*character) => None,
+                Event::ResponseWindow { waiting } => {
+                    Some(Event::ResponseWindow {
+                        waiting: redact_waiting(waiting, own),
+                    })
+                }
+                event => Some(event.clone()),
+            })
+            .collect()
+    }
+}
+
+fn redact_waiting(
+    waiting: &[CharacterIdx],
+    own: Option<CharacterIdx>
+) -> Vec::<CharacterIdx> {
+    waiting.iter().copied().filter(|&cid| Some(cid) == own).collect()
+}
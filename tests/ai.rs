@@ -0,0 +1,137 @@
+mod common;
+
+use common::*;
+use kier::ai::{Controller, Greedy, Mcts};
+use kier::*;
+
+// Card 0 strikes an enemy for 5, card 1 hurts its own player.
+fn ai_rules() -> Rules {
+    rules(vec![
+        damage("strike", Targets::Enemy, -5),
+        damage("blunder", Targets::Own, -5),
+    ])
+}
+
+fn started(rules: &Rules, cards: &[CardID]) -> Encounter {
+    let mut encounter = encounter(rules, 2, cards);
+    encounter.start();
+    encounter
+}
+
+// Always asks for a card that is not in hand.
+struct Clumsy;
+
+impl Controller for Clumsy {
+    fn decide(&mut self, _: &Encounter, character: CharacterIdx) -> Command {
+        Command::PlayCard { character, target: 0, index: 99 }
+    }
+}
+
+#[test]
+fn lists_legal_moves() {
+    let rules = ai_rules();
+    let encounter = started(&rules, &[0, 0, 0, 0, 0, 1]);
+    let moves = encounter.legal_moves();
+    assert_eq!(moves.last(), Some(&Command::EndTurn));
+    let plays = moves.iter().filter(|command| matches!(
+        command,
+        Command::PlayCard { character: 0, .. }
+    )).count();
+    assert_eq!(plays, 5);
+    assert!(moves.iter().all(|command| match *command {
+        Command::PlayCard { target, index, .. } => {
+            encounter.legal_targets(0, index).contains(&target)
+        }
+        _ => true,
+    }));
+}
+
+#[test]
+fn greedy_plays_what_helps() {
+    let rules = ai_rules();
+    let encounter = started(&rules, &[0; 10]);
+    let command = Greedy::new().decide(&encounter, 0);
+    assert!(matches!(
+        command,
+        Command::PlayCard { character: 0, target: 1, .. }
+    ));
+
+    let encounter = started(&rules, &[1; 10]);
+    assert_eq!(Greedy::new().decide(&encounter, 0), Command::EndTurn);
+}
+
+#[test]
+fn greedy_plays_harmless_cards() {
+    let rules = rules(vec![damage("feint", Targets::Enemy, 0)]);
+    let encounter = started(&rules, &[0; 10]);
+    let command = Greedy::new().decide(&encounter, 0);
+    assert!(matches!(command, Command::PlayCard { character: 0, .. }));
+
+    let mut game = game(&rules, 2, &[0; 10], 1);
+    game.set_controller(0, Box::new(Greedy::new()));
+    assert_eq!(game.run_controllers(), 6);
+    assert_eq!(game.encounter.characters[0].deck.discard.len(), 5);
+}
+
+#[test]
+fn mcts_finds_the_win() {
+    let rules = ai_rules();
+    let mut encounter = started(&rules, &[1, 1, 1, 1, 0, 1, 1, 1]);
+    encounter.set_trait(1, HEALTH, 5);
+    let before = replay::checksum(&encounter);
+    let strike = encounter.characters[0].deck.hand.iter()
+        .position(|&slot| slot == 4);
+
+    let mut mcts = Mcts::new(3);
+    mcts.iterations = 200;
+    let command = mcts.decide(&encounter, 0);
+    assert_eq!(replay::checksum(&encounter), before);
+    match strike {
+        Some(index) => assert_eq!(
+            command,
+            Command::PlayCard { character: 0, target: 1, index }
+        ),
+        None => assert_eq!(command, Command::EndTurn),
+    }
+}
+
+#[test]
+fn mcts_is_deterministic() {
+    let rules = ai_rules();
+    let encounter = started(&rules, &[0, 1, 0, 1, 0, 1, 0, 1, 0, 1]);
+    let decide = |seed| {
+        let mut mcts = Mcts::new(seed);
+        mcts.iterations = 50;
+        mcts.decide(&encounter, 0)
+    };
+    assert_eq!(decide(8), decide(8));
+}
+
+#[test]
+fn counts_applied_commands() {
+    let rules = ai_rules();
+    let mut game = game(&rules, 2, &[1; 10], 1);
+    game.set_controller(0, Box::new(Clumsy));
+    game.set_controller(1, Box::new(Clumsy));
+
+    // Each rejected play falls back to ending the turn.
+    assert_eq!(game.run_controllers_until(1), 2);
+    assert_eq!(game.encounter.round, 2);
+    assert_eq!(game.recording.steps.len(), 4);
+    assert_eq!(
+        game.recording.steps.iter().filter(|step| step.accepted).count(),
+        2
+    );
+}
+
+#[test]
+fn stops_for_uncontrolled_characters() {
+    let rules = rules(vec![damage("poke", Targets::Enemy, -1)]);
+    let mut game = game(&rules, 2, &[0; 10], 1);
+    game.set_controller(0, Box::new(Greedy::new()));
+    let count = game.run_controllers();
+    assert!(count >= 2);
+    assert!(!game.encounter.done);
+    assert_eq!(game.encounter.actor(), 1);
+    assert_eq!(game.run_controllers(), 0);
+}
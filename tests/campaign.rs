@@ -0,0 +1,236 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::*;
+use kier::campaign::{Node, NodeKind, Offer, RunError, RunStatus};
+use kier::*;
+
+const GOLD: TraitID = 2;
+
+fn campaign_rules() -> Rules {
+    let mut rules = rules(vec![
+        damage("smite", Targets::Enemy, -25),
+        damage("mend", Targets::Own, 5),
+    ]);
+    rules.trait_list = Arc::new(campaign_traits());
+    rules
+}
+
+fn campaign_traits() -> Vec::<Trait> {
+    let mut gold = Trait::new("gold");
+    gold.default = 10;
+    let mut traits = traits();
+    traits.push(gold);
+    traits
+}
+
+fn node(kind: NodeKind, next: &[usize]) -> Node {
+    Node { kind, next: next.to_vec() }
+}
+
+// A fight, then a choice of a reward or a rest, then a shop ending the
+// map.
+fn map() -> Vec::<Node> {
+    let enemy = Player { id: 9, character: character(1, &[0; 10]) };
+    vec![
+        node(NodeKind::Encounter {
+            enemies: vec![enemy],
+            features: Vec::new(),
+        }, &[1, 2]),
+        node(NodeKind::Reward { choices: vec![1] }, &[3]),
+        node(NodeKind::Rest {
+            id: HEALTH,
+            refill: Refill::Add(100),
+        }, &[3]),
+        node(NodeKind::Shop {
+            currency: GOLD,
+            offers: vec![Offer { card: 1, price: 4 }],
+        }, &[]),
+    ]
+}
+
+fn party() -> Vec::<Player> {
+    vec![Player { id: 1, character: character(0, &[0; 10]) }]
+}
+
+fn win_fight(run: &mut Run, rules: &Rules) {
+    let mut game = run.start_encounter(rules).unwrap();
+    game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }).unwrap();
+    assert_eq!(game.outcome().unwrap().winner, Some(0));
+    run.finish_encounter(&game).unwrap();
+}
+
+#[test]
+fn walks_the_map() {
+    let rules = campaign_rules();
+    let mut run = Run::new(map(), 0, party(), 4).unwrap();
+    assert_eq!(run.advance(1), Err(RunError::NodeNotCleared));
+    assert_eq!(run.take_reward(0, None), Err(RunError::WrongNode));
+    win_fight(&mut run, &rules);
+    assert!(run.cleared);
+    assert_eq!(run.start_encounter(&rules).err(), Some(RunError::NodeCleared));
+    assert_eq!(run.advance(3), Err(RunError::NoSuchNode(3)));
+
+    run.advance(1).unwrap();
+    assert_eq!(run.take_reward(0, Some(1)), Err(RunError::NoSuchChoice(1)));
+    run.take_reward(0, Some(0)).unwrap();
+    let deck = &run.party[0].character.deck;
+    assert_eq!(deck.clist.len(), 11);
+    assert_eq!(deck.deck.len(), 11);
+
+    run.advance(3).unwrap();
+    assert_eq!(run.visited, vec![0, 1, 3]);
+    assert_eq!(run.status, RunStatus::Won);
+}
+
+#[test]
+fn rest_restores_within_bounds() {
+    let mut rules = campaign_rules();
+    let mut traits = campaign_traits();
+    traits[HEALTH as usize].max = 30;
+    rules.trait_list = Arc::new(traits);
+
+    let mut run = Run::new(map(), 0, party(), 4).unwrap();
+    win_fight(&mut run, &rules);
+    run.advance(2).unwrap();
+    run.rest(&rules).unwrap();
+    assert_eq!(run.party[0].character.traits()[&HEALTH], 30);
+    assert_eq!(run.rest(&rules), Err(RunError::NodeCleared));
+}
+
+// Rests on health, refilled to `refill`, with constitution and a max
+// health derived from it added to the campaign's traits.
+fn rest_to(refill: Refill, constitution: TraitValue) -> TraitValue {
+    const CONSTITUTION: TraitID = 3;
+    fn max_health(encounter: &Encounter, cid: CharacterIdx) -> TraitValue {
+        encounter.get_trait(cid, CONSTITUTION) * 10
+    }
+
+    let mut rules = campaign_rules();
+    let mut traits = campaign_traits();
+    traits.push(Trait::new("constitution"));
+    let mut max = Trait::new("max health");
+    max.derive = Some(max_health);
+    traits.push(max);
+    rules.trait_list = Arc::new(traits);
+
+    let mut map = map();
+    map[2].kind = NodeKind::Rest { id: HEALTH, refill };
+    let mut traits = HashMap::new();
+    traits.insert(HEALTH, 1);
+    traits.insert(CONSTITUTION, constitution);
+    let character = Character::new(traits, Deck::new(&[0; 10]));
+    let party = vec![Player { id: 1, character }];
+    let mut run = Run::new(map, 2, party, 4).unwrap();
+    run.rest(&rules).unwrap();
+    run.party[0].character.traits()[&HEALTH]
+}
+
+#[test]
+fn rest_refills_to_stored_traits() {
+    assert_eq!(rest_to(Refill::ToTrait(GOLD), 3), 10);
+    assert_eq!(rest_to(Refill::ToTrait(3), 3), 3);
+}
+
+#[test]
+fn rest_refills_to_derived_traits() {
+    assert_eq!(rest_to(Refill::ToTrait(4), 3), 30);
+    assert_eq!(rest_to(Refill::ToTrait(4), 1), 10);
+}
+
+#[test]
+fn terminal_shop_stays_open() {
+    let rules = campaign_rules();
+    let mut run = Run::new(map(), 3, party(), 4).unwrap();
+    assert_eq!(run.status, RunStatus::Won);
+
+    // Gold is not stored yet, so the trait's default is spent.
+    run.buy(&rules, 0, 0).unwrap();
+    let character = &run.party[0].character;
+    assert_eq!(character.traits()[&GOLD], 6);
+    assert_eq!(character.deck.clist.last(), Some(&Some(1)));
+    assert_eq!(run.buy(&rules, 0, 0), Err(RunError::NoSuchChoice(0)));
+}
+
+#[test]
+fn shop_checks_funds() {
+    let rules = campaign_rules();
+    let mut run = Run::new(map(), 3, party(), 4).unwrap();
+    run.party[0].character = {
+        let mut traits = HashMap::new();
+        traits.insert(GOLD, 3);
+        Character::new(traits, Deck::new(&[0; 10]))
+    };
+    assert_eq!(
+        run.buy(&rules, 0, 0),
+        Err(RunError::CannotAfford { price: 4, available: 3 })
+    );
+    assert_eq!(run.buy(&rules, 1, 0), Err(RunError::NoSuchCharacter(1)));
+}
+
+#[test]
+fn losing_ends_the_run() {
+    let rules = campaign_rules();
+    let mut run = Run::new(map(), 0, party(), 4).unwrap();
+    let mut game = run.start_encounter(&rules).unwrap();
+    assert_eq!(run.finish_encounter(&game), Err(RunError::EncounterNotOver));
+    game.apply(Command::Concede { character: 0 }).unwrap();
+    run.finish_encounter(&game).unwrap();
+    assert_eq!(run.status, RunStatus::Lost);
+    assert_eq!(run.advance(1), Err(RunError::RunOver));
+}
+
+#[test]
+fn refuses_foreign_games() {
+    let rules = campaign_rules();
+    let mut run = Run::new(map(), 0, party(), 4).unwrap();
+    let other = Run::new(map(), 0, party(), 5).unwrap();
+    let mut game = other.start_encounter(&rules).unwrap();
+    game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }).unwrap();
+    assert_eq!(run.finish_encounter(&game), Err(RunError::ForeignGame));
+
+    let mut game = Game::new(party(), Vec::new(), &rules, 1);
+    game.apply(Command::Concede { character: 0 }).unwrap();
+    assert_eq!(run.finish_encounter(&game), Err(RunError::ForeignGame));
+    assert_eq!(run.status, RunStatus::InProgress);
+}
+
+#[test]
+fn checks_node_indices() {
+    assert_eq!(
+        Run::new(map(), 4, party(), 4).err(),
+        Some(RunError::MissingNode(4))
+    );
+    let mut map = map();
+    map[1].next.push(7);
+    assert_eq!(
+        Run::new(map, 0, party(), 4).err(),
+        Some(RunError::MissingNode(7))
+    );
+}
+
+#[test]
+fn resumes_saved_runs() {
+    let rules = campaign_rules();
+    let mut run = Run::new(map(), 0, party(), 4).unwrap();
+    win_fight(&mut run, &rules);
+    let json = serde_json::to_string(&run).unwrap();
+
+    let mut resumed: Run = serde_json::from_str(&json).unwrap();
+    resumed = resumed.resume().unwrap();
+    resumed.advance(2).unwrap();
+
+    for version in [0, 2] {
+        let mut saved: Run = serde_json::from_str(&json).unwrap();
+        saved.version = version;
+        assert_eq!(
+            saved.resume().err(),
+            Some(RunError::UnsupportedVersion(version))
+        );
+    }
+    let mut saved: Run = serde_json::from_str(&json).unwrap();
+    saved.position = 12;
+    assert_eq!(saved.resume().err(), Some(RunError::MissingNode(12)));
+}
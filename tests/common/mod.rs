@@ -0,0 +1,67 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use kier::effect::{self, Who};
+use kier::*;
+
+// Trait 0 is health and decides defeat, trait 1 is energy.
+pub const HEALTH: TraitID = 0;
+pub const ENERGY: TraitID = 1;
+
+pub fn traits() -> Vec::<Trait> {
+    let mut health = Trait::new("health");
+    health.default = 20;
+    let mut energy = Trait::new("energy");
+    energy.min = 0;
+    energy.default = 3;
+    vec![health, energy]
+}
+
+// A card that changes the health of its target by `by`.
+pub fn damage(name: &str, targets: Targets, by: TraitValue) -> Card {
+    let mut card = Card::new(name, "", effect::no_effect);
+    card.targets = targets;
+    card.effects.push(Effect::ModifyTrait { who: Who::Target, id: HEALTH, by });
+    card
+}
+
+// A feature effect that does nothing, for features that only trigger or
+// react.
+pub fn idle(_: &mut Encounter, _: FeatureIdx) {}
+
+pub fn rules(cards: Vec::<Card>) -> Rules {
+    let mut rules = Rules::new(Arc::new(cards), Arc::new(Vec::new()));
+    rules.trait_list = Arc::new(traits());
+    rules.end_conditions.push(EndCondition::Defeat { id: HEALTH });
+    rules
+}
+
+pub fn character(side: usize, cards: &[CardID]) -> Character {
+    let mut character = Character::new(HashMap::new(), Deck::new(cards));
+    character.side = side;
+    character
+}
+
+// One player per side, numbered from 1, each with `cards` as their deck.
+pub fn players(sides: usize, cards: &[CardID]) -> Vec::<Player> {
+    (0..sides)
+        .map(|side| Player {
+            id: side as PlayerID + 1,
+            character: character(side, cards),
+        })
+        .collect()
+}
+
+pub fn game(rules: &Rules, sides: usize, cards: &[CardID], seed: u64) -> Game {
+    Game::new(players(sides, cards), Vec::new(), rules, seed)
+}
+
+pub fn encounter(rules: &Rules, sides: usize, cards: &[CardID]) -> Encounter {
+    let characters = players(sides, cards)
+        .into_iter()
+        .map(|p| p.character)
+        .collect();
+    Encounter::new(characters, Vec::new(), rules, 1)
+}
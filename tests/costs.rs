@@ -0,0 +1,77 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::loader::parse_cards;
+use kier::*;
+
+fn priced(cost: TraitValue) -> Rules {
+    let mut card = damage("zap", Targets::Enemy, -1);
+    card.cost.push((ENERGY, cost));
+    rules(vec![card])
+}
+
+#[test]
+fn pays_from_base_value() {
+    let rules = priced(2);
+    let mut game = game(&rules, 2, &[0; 10], 1);
+    assert!(game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }).is_ok());
+    assert_eq!(game.encounter.get_trait(0, ENERGY), 1);
+    assert_eq!(
+        game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }),
+        Err(ActionError::CannotAfford { id: ENERGY, cost: 2, available: 1 })
+    );
+    assert_eq!(game.encounter.get_trait(0, ENERGY), 1);
+}
+
+#[test]
+fn modifiers_do_not_pay() {
+    let mut rules = priced(1);
+    let mut charged = Status::new("charged", "");
+    charged.modifiers.push((ENERGY, 3));
+    rules.status_list = Arc::new(vec![charged]);
+
+    let mut game = game(&rules, 2, &[0; 10], 1);
+    let enc = &mut game.encounter;
+    enc.set_trait(0, ENERGY, 0);
+    enc.apply_status(0, 0, 1, None);
+    assert_eq!(enc.get_trait(0, ENERGY), 3);
+    assert_eq!(
+        enc.play_card(0, 1, 0),
+        Err(ActionError::CannotAfford { id: ENERGY, cost: 1, available: 0 })
+    );
+
+    enc.set_trait(0, ENERGY, 1);
+    assert!(enc.play_card(0, 1, 0).is_ok());
+    assert_eq!(enc.base_trait(0, ENERGY), 0);
+    assert_eq!(enc.get_trait(0, ENERGY), 3);
+    assert!(enc.play_card(0, 1, 0).is_err());
+}
+
+#[test]
+fn refills_each_turn() {
+    let mut rules = priced(3);
+    let mut traits = traits();
+    traits[ENERGY as usize].refill = Some(Refill::Set(3));
+    rules.trait_list = Arc::new(traits);
+
+    let mut game = game(&rules, 2, &[0; 20], 1);
+    assert!(game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }).is_ok());
+    assert_eq!(game.encounter.get_trait(0, ENERGY), 0);
+    game.apply(Command::EndTurn).unwrap();
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(game.encounter.get_trait(0, ENERGY), 3);
+}
+
+#[test]
+fn loader_rejects_free_costs() {
+    let traits = traits();
+    for amount in ["0", "-1", "-9223372036854775808"] {
+        let src = format!("card Zap\ncost energy {}\n", amount);
+        let err = parse_cards(&src, "cards", &traits).err().unwrap();
+        assert_eq!(err.line, 2);
+    }
+    let cards = parse_cards("card Zap\ncost energy 2\n", "cards", &traits).unwrap();
+    assert_eq!(cards[0].cost, vec![(ENERGY, 2)]);
+}
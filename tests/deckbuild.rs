@@ -0,0 +1,82 @@
+mod common;
+
+use common::*;
+use kier::*;
+
+// Card 0 is neutral, 1 belongs to mages and 2 to warriors.
+fn cards() -> Vec::<Card> {
+    let mut fireball = damage("fireball", Targets::Enemy, -4);
+    fireball.class = Some("mage".to_string());
+    let mut cleave = damage("cleave", Targets::AllEnemies, -2);
+    cleave.class = Some("warrior".to_string());
+    vec![damage("strike", Targets::Enemy, -1), fireball, cleave]
+}
+
+#[test]
+fn legal_decks_build() {
+    let rules = DeckRules::new(4, 6, 3);
+    let list = [0, 1, 0, 1, 0];
+    assert!(rules.validate(&list, Some("mage"), &cards()).is_empty());
+    let deck = rules.build(&list, Some("mage"), &cards()).ok().unwrap();
+    assert_eq!(deck.clist, list.iter().map(|&id| Some(id)).collect::<Vec::<_>>());
+    assert_eq!(deck.deck.len(), 5);
+    assert!(deck.hand.is_empty() && deck.discard.is_empty());
+}
+
+#[test]
+fn sizes_and_copies() {
+    let rules = DeckRules::new(4, 6, 2);
+    assert_eq!(rules.validate(&[0, 0], None, &cards()), vec![
+        DeckViolation::TooFew { size: 2, min: 4 },
+    ]);
+    assert_eq!(rules.validate(&[0; 7], None, &cards()), vec![
+        DeckViolation::TooMany { size: 7, max: 6 },
+        DeckViolation::TooManyCopies { card: 0, count: 7, max: 2 },
+    ]);
+}
+
+#[test]
+fn classes_and_bans() {
+    let mut rules = DeckRules::new(0, 10, 4);
+    rules.banned.push(0);
+    rules.class_banned.insert("warrior".to_string(), vec![2]);
+
+    assert_eq!(rules.validate(&[2, 1, 0, 9], Some("warrior"), &cards()), vec![
+        DeckViolation::Banned(2),
+        DeckViolation::WrongClass { card: 1, class: "mage".to_string() },
+        DeckViolation::Banned(0),
+        DeckViolation::UnknownCard(9),
+    ]);
+    assert_eq!(rules.validate(&[1, 2], None, &cards()), vec![
+        DeckViolation::WrongClass { card: 1, class: "mage".to_string() },
+        DeckViolation::WrongClass { card: 2, class: "warrior".to_string() },
+    ]);
+    assert_eq!(
+        rules.build(&[2], Some("warrior"), &cards()).err(),
+        Some(vec![DeckViolation::Banned(2)])
+    );
+    assert_eq!(rules.validate(&[2], Some("mage"), &cards()), vec![
+        DeckViolation::WrongClass { card: 2, class: "warrior".to_string() },
+    ]);
+}
+
+#[test]
+fn violations_describe_themselves() {
+    let cases = [
+        (
+            DeckViolation::TooFew { size: 2, min: 4 },
+            "deck has 2 cards, fewer than the minimum of 4",
+        ),
+        (
+            DeckViolation::TooManyCopies { card: 0, count: 7, max: 2 },
+            "deck has 7 copies of card 0, more than 2",
+        ),
+        (
+            DeckViolation::WrongClass { card: 1, class: "mage".to_string() },
+            "card 1 belongs to class mage",
+        ),
+    ];
+    for (violation, message) in cases {
+        assert_eq!(violation.to_string(), message);
+    }
+}
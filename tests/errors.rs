@@ -0,0 +1,140 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+// Card 0 is free, card 1 costs more energy than anyone has, and the
+// encounter has feature 0, plus a feature id missing from the list.
+fn setup() -> Encounter {
+    let mut pricey = damage("pricey", Targets::Enemy, -1);
+    pricey.cost.push((ENERGY, 9));
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1), pricey]);
+    rules.feature_list = Arc::new(vec![Feature::new("altar", "", idle)]);
+    let characters = (0..2)
+        .map(|side| character(side, &[0, 1, 0, 0, 0, 0]))
+        .collect();
+    let mut encounter = Encounter::new(characters, vec![0, 7], &rules, 1);
+    encounter.draw_count = 6;
+    encounter.start();
+    encounter
+}
+
+fn hand_index(encounter: &Encounter, kind: CardID) -> usize {
+    let deck = &encounter.characters[0].deck;
+    deck.hand.iter()
+        .position(|&slot| deck.clist[slot as usize] == Some(kind))
+        .unwrap()
+}
+
+// Rejected actions leave the encounter exactly as it was.
+fn rejected(
+    encounter: &mut Encounter,
+    action: impl FnOnce(&mut Encounter) -> Result<(), ActionError>
+) -> ActionError {
+    let checksum = replay::checksum(encounter);
+    let events = encounter.events().len();
+    let err = action(encounter).unwrap_err();
+    assert_eq!(replay::checksum(encounter), checksum, "{:?}", err);
+    assert_eq!(encounter.events().len(), events, "{:?}", err);
+    err
+}
+
+#[test]
+fn play_card_errors() {
+    let mut encounter = setup();
+    let pricey = hand_index(&encounter, 1);
+    let hit = hand_index(&encounter, 0);
+
+    assert_eq!(
+        rejected(&mut encounter, |e| e.play_card(5, 1, 0)),
+        ActionError::NoSuchCharacter(5)
+    );
+    assert_eq!(
+        rejected(&mut encounter, |e| e.play_card(0, 1, 6)),
+        ActionError::NoSuchHandCard(6)
+    );
+    assert_eq!(
+        rejected(&mut encounter, |e| e.play_card(0, 0, hit)),
+        ActionError::IllegalTarget { target: 0, targets: Targets::Enemy }
+    );
+    assert_eq!(
+        rejected(&mut encounter, |e| e.play_card(0, 1, pricey)),
+        ActionError::CannotAfford { id: ENERGY, cost: 9, available: 3 }
+    );
+
+    let slot = encounter.characters[0].deck.hand[hit] as usize;
+    encounter.characters[0].deck.clist[slot] = None;
+    assert_eq!(
+        rejected(&mut encounter, |e| e.play_card(0, 1, hit)),
+        ActionError::EmptySlot(slot as CardID)
+    );
+    encounter.characters[0].deck.clist[slot] = Some(40);
+    assert_eq!(
+        rejected(&mut encounter, |e| e.play_card(0, 1, hit)),
+        ActionError::UnknownCard(40)
+    );
+}
+
+#[test]
+fn activate_feature_errors() {
+    let mut encounter = setup();
+    assert_eq!(
+        rejected(&mut encounter, |e| e.activate_feature(2)),
+        ActionError::NoSuchFeature(2)
+    );
+    assert_eq!(
+        rejected(&mut encounter, |e| e.activate_feature(1)),
+        ActionError::UnknownFeature(7)
+    );
+    encounter.destroy_feature(0);
+    assert_eq!(
+        rejected(&mut encounter, |e| e.activate_feature(0)),
+        ActionError::FeatureDestroyed(0)
+    );
+    encounter.end();
+    assert_eq!(
+        rejected(&mut encounter, |e| e.activate_feature(0)),
+        ActionError::EncounterOver
+    );
+}
+
+#[test]
+fn games_record_rejections() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut game = game(&rules, 2, &[0; 10], 1);
+    let command = Command::PlayCard { character: 1, target: 0, index: 0 };
+    assert_eq!(
+        game.apply(command),
+        Err(ActionError::NotYourTurn { character: 1, active: 0 })
+    );
+    let step = game.recording.steps.last().unwrap();
+    assert_eq!(step.command, command);
+    assert!(!step.accepted);
+}
+
+#[test]
+fn errors_describe_themselves() {
+    let cases = [
+        (ActionError::EncounterOver, "the encounter is over"),
+        (ActionError::WrongPhase(Phase::Setup), "not allowed during the Setup phase"),
+        (
+            ActionError::NotYourTurn { character: 1, active: 0 },
+            "character 1 acted during the turn of 0",
+        ),
+        (ActionError::NoSuchHandCard(4), "no card 4 in hand"),
+        (ActionError::EmptySlot(3), "deck slot 3 holds no card"),
+        (
+            ActionError::IllegalTarget { target: 2, targets: Targets::Ally },
+            "2 is not a legal target for a card targeting Ally",
+        ),
+        (
+            ActionError::CannotAfford { id: 1, cost: 3, available: 2 },
+            "costs 3 of trait 1 but only 2 is available",
+        ),
+    ];
+    for (err, message) in cases {
+        assert_eq!(err.to_string(), message);
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+fn hurt_active(encounter: &mut Encounter, _: FeatureIdx) {
+    let cid = encounter.active;
+    encounter.modify_trait(cid, HEALTH, -2);
+}
+
+fn started(rules: &Rules) -> Encounter {
+    let mut encounter = encounter(rules, 2, &[0; 10]);
+    encounter.start();
+    encounter
+}
+
+#[test]
+fn logs_card_plays_and_trait_changes() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -6)]);
+    let mut encounter = started(&rules);
+    let before = encounter.events().to_vec();
+    let card = encounter.characters[0].deck.hand[2];
+    encounter.play_card(0, 1, 2).unwrap();
+
+    let events = encounter.events();
+    assert_eq!(&events[..before.len()], &before[..]);
+    assert_eq!(&events[before.len()..], &[
+        Event::CardPlayed { character: 0, target: 1, card, kind: 0 },
+        Event::TraitChanged { character: 1, id: HEALTH, old: Some(20), new: 14 },
+    ]);
+}
+
+#[test]
+fn logs_draws_discards_and_reshuffles() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut encounter = encounter(&rules, 2, &[0; 2]);
+    let first = encounter.draw_card(1).unwrap();
+    let second = encounter.draw_card(1).unwrap();
+    assert!(encounter.discard_card(1, 0));
+    assert!(!encounter.discard_card(1, 5));
+    let third = encounter.draw_card(1).unwrap();
+    assert_eq!(third, first);
+    assert_eq!(encounter.events(), &[
+        Event::CardDrawn { character: 1, card: first },
+        Event::CardDrawn { character: 1, card: second },
+        Event::Discarded { character: 1, card: first },
+        Event::Reshuffled { character: 1 },
+        Event::CardDrawn { character: 1, card: first },
+    ]);
+}
+
+#[test]
+fn unchanged_traits_are_not_logged() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    assert!(encounter.set_trait(0, HEALTH, 20));
+    assert!(encounter.modify_trait(0, ENERGY, 0));
+    assert!(encounter.set_trait(0, ENERGY, -5));
+    assert!(encounter.modify_trait(0, ENERGY, -1));
+    assert_eq!(encounter.events(), &[
+        Event::TraitChanged { character: 0, id: ENERGY, old: Some(3), new: 0 },
+    ]);
+}
+
+#[test]
+fn logs_features_and_the_end() {
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    rules.feature_list = Arc::new(vec![Feature::new("thorns", "", hurt_active)]);
+    let characters = (0..2).map(|side| character(side, &[0; 10])).collect();
+    let mut encounter = Encounter::new(characters, vec![0], &rules, 1);
+    encounter.start();
+    encounter.set_trait(0, HEALTH, 2);
+    let before = encounter.events().len();
+    encounter.activate_feature(0).unwrap();
+
+    let outcome = Outcome { winner: Some(1), reason: EndReason::Defeat };
+    assert_eq!(&encounter.events()[before..], &[
+        Event::FeatureActivated { feature: 0, id: 0 },
+        Event::TraitChanged { character: 0, id: HEALTH, old: Some(2), new: 0 },
+        Event::EncounterEnded { outcome: Some(outcome) },
+    ]);
+
+    // Only one end is ever logged.
+    encounter.end();
+    assert_eq!(encounter.events().len(), before + 3);
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::*;
+use kier::loader::{
+    load_rules, parse_cards, parse_deck, parse_traits, RulesError,
+    MAX_DECK_LIST,
+};
+use kier::*;
+
+const CARDS: &str = "
+# Two plain cards.
+card Strike
+description Deal 6 damage.
+target enemy
+cost energy 1
+effect modify target health -6
+
+card Guard
+target self
+effect modify player health 4
+effect draw player 1
+";
+
+fn cards() -> Vec::<Card> {
+    parse_cards(CARDS, "cards", &traits()).unwrap()
+}
+
+fn deck_error(src: &str) -> (usize, String) {
+    let err = parse_deck(src, "deck", &cards()).unwrap_err();
+    assert_eq!(err.file, "deck");
+    (err.line, err.message)
+}
+
+#[test]
+fn parses_cards() {
+    let cards = cards();
+    assert_eq!(cards.len(), 2);
+    assert_eq!(cards[0].name, "Strike");
+    assert_eq!(cards[0].description, "Deal 6 damage.");
+    assert_eq!(cards[0].targets, Targets::Enemy);
+    assert_eq!(cards[0].cost, vec![(ENERGY, 1)]);
+    assert_eq!(cards[1].targets, Targets::Own);
+    assert_eq!(cards[1].effects.len(), 2);
+}
+
+#[test]
+fn rejects_duplicate_card_names() {
+    let src = format!("{}\ncard Strike\ntarget any\n", CARDS);
+    let err = parse_cards(&src, "cards", &traits()).err().unwrap();
+    assert_eq!(err.line, 14);
+    assert_eq!(err.message, "duplicate card 'Strike'");
+    assert_eq!(err.to_string(), "cards:14: duplicate card 'Strike'");
+}
+
+#[test]
+fn card_errors_name_the_line() {
+    let cases = [
+        ("target enemy", 1, "'target' before any card"),
+        ("card", 1, "card needs a name"),
+        ("card A\ntarget sideways", 2, "unknown target 'sideways'"),
+        ("card A\neffect modify someone health 1", 2,
+            "expected 'player' or 'target', found 'someone'"),
+        ("card A\neffect modify target luck 1", 2, "unknown trait 'luck'"),
+        ("card A\neffect draw player", 2, "effect 'draw' takes 2 arguments"),
+        ("card A\n\nflavour none", 3, "unknown key 'flavour'"),
+        ("card A\nscript let x = ;", 2, "expected a name"),
+    ];
+    for (src, line, message) in cases {
+        let err = parse_cards(src, "cards", &traits()).err().unwrap();
+        assert_eq!((err.line, err.message.as_str()), (line, message), "{:?}", src);
+    }
+}
+
+#[test]
+fn parses_decks() {
+    let list = parse_deck("# starter\n3 Strike\n\nGuard\n2 Guard\n", "deck", &cards());
+    assert_eq!(list.unwrap(), vec![0, 0, 0, 1, 1, 1]);
+    assert_eq!(deck_error("2 Strike\n4 Bash"), (2, "unknown card 'Bash'".to_string()));
+}
+
+#[test]
+fn caps_deck_lists() {
+    let full = format!("{} Strike", MAX_DECK_LIST);
+    assert_eq!(parse_deck(&full, "deck", &cards()).unwrap().len(), MAX_DECK_LIST);
+
+    let message = format!("deck has more than {} cards", MAX_DECK_LIST);
+    let over = format!("{} Strike\nGuard", MAX_DECK_LIST);
+    assert_eq!(deck_error(&over), (2, message.clone()));
+    let huge = format!("Guard\n{} Strike", usize::MAX);
+    assert_eq!(deck_error(&huge), (2, message));
+
+    // Counts too large for a number are read as part of the name.
+    assert_eq!(
+        deck_error("99999999999999999999999 Strike").1,
+        "unknown card '99999999999999999999999 Strike'"
+    );
+}
+
+#[test]
+fn parses_trait_specs() {
+    let specs = |specs: &[&str]| -> Vec::<String> {
+        specs.iter().map(|spec| spec.to_string()).collect()
+    };
+    let traits = parse_traits(
+        &specs(&["health=30", "energy=0"]),
+        &specs(&["energy=3"])
+    ).unwrap();
+    assert_eq!(traits.len(), 2);
+    assert_eq!((traits[0].default, traits[0].refill), (30, None));
+    assert_eq!(traits[1].refill, Some(Refill::Set(3)));
+
+    let cases = [
+        (specs(&["health"]), specs(&[]), RulesError::BadSpec("health".into())),
+        (specs(&["health=x"]), specs(&[]), RulesError::BadSpec("health=x".into())),
+        (specs(&[]), specs(&["luck=1"]), RulesError::UnknownTrait("luck".into())),
+    ];
+    for (traits, refills, err) in cases {
+        assert_eq!(parse_traits(&traits, &refills).err(), Some(err));
+    }
+
+    let cards = std::path::Path::new("no-such-cards");
+    let rules = |traits: &[&str], defeat| {
+        load_rules(cards, &specs(traits), &[], defeat).err().unwrap()
+    };
+    assert_eq!(rules(&[], None), RulesError::NoDefeatTrait);
+    assert_eq!(
+        rules(&["health=30"], Some("luck")),
+        RulesError::UnknownTrait("luck".into())
+    );
+    assert!(matches!(rules(&["health=30"], None), RulesError::Cards(_)));
+}
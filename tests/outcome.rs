@@ -0,0 +1,162 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+fn side_one_at_round_three(encounter: &Encounter) -> Option<Outcome> {
+    (encounter.round >= 3).then(|| Outcome {
+        winner: Some(1),
+        reason: EndReason::Custom("late".to_string()),
+    })
+}
+
+fn outcome(winner: Option<usize>, reason: EndReason) -> Option<Outcome> {
+    Some(Outcome { winner, reason })
+}
+
+// Characters 0 and 1 fight together against 2.
+fn teams(rules: &Rules) -> Encounter {
+    let characters = [0, 0, 1]
+        .iter()
+        .map(|&side| character(side, &[0; 10]))
+        .collect();
+    let mut encounter = Encounter::new(characters, vec![0], rules, 1);
+    encounter.start();
+    encounter
+}
+
+#[test]
+fn last_side_standing_wins() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -20)]);
+    let mut encounter = teams(&rules);
+    encounter.set_trait(0, HEALTH, 0);
+    assert!(!encounter.check_end());
+
+    // A defeated ally leaves its side standing.
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.active, 1);
+    encounter.play_card(1, 2, 0).unwrap();
+    assert!(encounter.done);
+    assert_eq!(encounter.outcome, outcome(Some(0), EndReason::Defeat));
+}
+
+#[test]
+fn mutual_defeat_is_a_draw() {
+    let rules = rules(Vec::new());
+    let mut encounter = teams(&rules);
+    for cid in 0..3 {
+        encounter.set_trait(cid, HEALTH, -1);
+    }
+    assert!(encounter.check_end());
+    assert_eq!(encounter.outcome, outcome(None, EndReason::Defeat));
+}
+
+#[test]
+fn one_side_never_loses_by_defeat() {
+    let rules = rules(Vec::new());
+    let characters = (0..2).map(|_| character(0, &[0; 10])).collect();
+    let mut encounter = Encounter::new(characters, Vec::new(), &rules, 1);
+    encounter.start();
+    encounter.set_trait(0, HEALTH, 0);
+    encounter.set_trait(1, HEALTH, 0);
+    assert!(!encounter.check_end());
+}
+
+#[test]
+fn surviving_and_destroying() {
+    let mut rules = rules(Vec::new());
+    rules.feature_list = Arc::new(vec![Feature::new("gate", "", idle)]);
+    rules.end_conditions = vec![
+        EndCondition::Survive { side: 1, rounds: 2 },
+        EndCondition::FeatureDestroyed { feature: 0, side: 0 },
+    ];
+
+    let mut encounter = teams(&rules);
+    for _ in 0..5 {
+        encounter.end_turn().unwrap();
+    }
+    assert_eq!((encounter.round, encounter.done), (2, false));
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.outcome, outcome(Some(1), EndReason::Survived));
+
+    let mut encounter = teams(&rules);
+    encounter.destroy_feature(0);
+    assert!(encounter.check_end());
+    assert_eq!(
+        encounter.outcome,
+        outcome(Some(0), EndReason::FeatureDestroyed)
+    );
+}
+
+#[test]
+fn first_condition_decides() {
+    let mut rules = rules(Vec::new());
+    rules.end_conditions = vec![
+        EndCondition::Custom(side_one_at_round_three),
+        EndCondition::Survive { side: 0, rounds: 2 },
+    ];
+    let mut encounter = teams(&rules);
+    while !encounter.done {
+        encounter.end_turn().unwrap();
+    }
+    assert_eq!(encounter.round, 3);
+    assert_eq!(
+        encounter.outcome,
+        outcome(Some(1), EndReason::Custom("late".to_string()))
+    );
+    assert_eq!(encounter.events().last(), Some(&Event::EncounterEnded {
+        outcome: encounter.outcome.clone(),
+    }));
+}
+
+#[test]
+fn conceding() {
+    let rules = rules(Vec::new());
+    let mut encounter = teams(&rules);
+    assert_eq!(encounter.concede(5), Err(ActionError::NoSuchCharacter(5)));
+    encounter.concede(2).unwrap();
+    assert_eq!(encounter.outcome, outcome(Some(0), EndReason::Conceded));
+    assert_eq!(encounter.concede(0), Err(ActionError::EncounterOver));
+
+    // With three sides, the others play on and skip the conceded turns.
+    let mut game = game(&rules, 3, &[0; 10], 1);
+    game.apply(Command::Concede { character: 1 }).unwrap();
+    assert!(game.outcome().is_none());
+    assert_eq!(
+        game.apply(Command::Concede { character: 1 }),
+        Err(ActionError::Conceded(1))
+    );
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(game.encounter.active, 2);
+
+    // The last character of another side conceding ends it.
+    game.apply(Command::Concede { character: 2 }).unwrap();
+    assert_eq!(game.outcome(), outcome(Some(0), EndReason::Conceded).as_ref());
+    assert_eq!(game.encounter.events().last(), Some(&Event::EncounterEnded {
+        outcome: game.outcome().cloned(),
+    }));
+}
+
+#[test]
+fn conceding_hands_on_the_turn() {
+    let rules = rules(Vec::new());
+    let mut game = game(&rules, 3, &[0; 10], 1);
+    game.apply(Command::Concede { character: 0 }).unwrap();
+    assert!(game.outcome().is_none());
+    assert_eq!(game.encounter.active, 1);
+    assert!(game.encounter.has_conceded(0));
+    game.apply(Command::EndTurn).unwrap();
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!((game.encounter.active, game.encounter.round), (1, 2));
+}
+
+#[test]
+fn winners_are_players() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -20)]);
+    let mut game = game(&rules, 2, &[0; 10], 1);
+    assert!(game.winners().is_empty());
+    game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }).unwrap();
+    assert_eq!(game.winners(), vec![1]);
+}
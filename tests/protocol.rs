@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use kier::effect::{self, Who};
+use kier::protocol::{
+    self, FrameError, ProtocolError, RoomInfo, MAX_FRAME, PROTOCOL_VERSION,
+};
+use kier::*;
+
+fn rules() -> Rules {
+    let mut strike = Card::new("strike", "", effect::no_effect);
+    strike.targets = Targets::Enemy;
+    strike.effects.push(Effect::ModifyTrait { who: Who::Target, id: 0, by: -3 });
+    let mut rules = Rules::new(Arc::new(vec![strike]), Arc::new(vec![]));
+    rules.end_conditions.push(EndCondition::Defeat { id: 0 });
+    rules
+}
+
+fn game(rules: &Rules) -> Game {
+    let players = (0..2)
+        .map(|side| {
+            let mut traits = HashMap::new();
+            traits.insert(0, 10);
+            let mut character = Character::new(traits, Deck::new(&[0; 6]));
+            character.side = side;
+            Player { id: side as PlayerID + 1, character }
+        })
+        .collect();
+    Game::new(players, vec![], rules, 7)
+}
+
+fn round_trip<T>(message: T)
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let frame = protocol::encode(&message).unwrap();
+    let (decoded, used) = protocol::decode::<T>(&frame).unwrap().unwrap();
+    assert_eq!(decoded, message);
+    assert_eq!(used, frame.len());
+
+    let read: T = protocol::read_frame(&mut Cursor::new(&frame)).unwrap();
+    assert_eq!(read, message);
+}
+
+#[test]
+fn requests_round_trip() {
+    let requests = vec![
+        Request::Hello { version: PROTOCOL_VERSION },
+        Request::Join { player: 42 },
+        Request::PlayCard { target: 1, index: 3 },
+        Request::ActivateFeature { feature: 2 },
+        Request::EndTurn,
+        Request::Respond { target: 0, index: 1 },
+        Request::RespondFeature { feature: 0 },
+        Request::Pass,
+        Request::Concede,
+        Request::ListRooms,
+        Request::CreateRoom { seats: 2 },
+        Request::JoinRoom { room: 3, deck: "5 Strike\n3 Heal\n".to_string() },
+        Request::Ready,
+        Request::LeaveRoom,
+        Request::Queue { deck: "Strike".to_string() },
+    ];
+    for request in requests {
+        round_trip(request);
+    }
+}
+
+#[test]
+fn responses_round_trip() {
+    let rules = rules();
+    let mut game = game(&rules);
+    game.handle(1, &Request::PlayCard { target: 1, index: 0 }).unwrap();
+    game.handle(1, &Request::Concede).unwrap();
+    let room = RoomInfo {
+        id: 1,
+        seats: 2,
+        members: vec![(1, true), (2, false)],
+        started: false,
+    };
+
+    let mut responses = vec![
+        Response::Welcome { version: PROTOCOL_VERSION },
+        Response::State(game.view(1)),
+        Response::State(game.view(99)),
+        Response::Error(ProtocolError::Action(ActionError::EncounterOver)),
+        Response::Error(ProtocolError::VersionMismatch { server: 1, client: 2 }),
+        Response::Error(ProtocolError::NoHandshake),
+        Response::Error(ProtocolError::NotJoined),
+        Response::Error(ProtocolError::AlreadyJoined),
+        Response::Error(ProtocolError::Unauthenticated),
+        Response::Error(ProtocolError::NoSuchPlayer(5)),
+        Response::Error(ProtocolError::SeatTaken(1)),
+        Response::Error(ProtocolError::NoSuchRoom(4)),
+        Response::Error(ProtocolError::RoomFull(4)),
+        Response::Error(ProtocolError::InvalidSeats(0)),
+        Response::Error(ProtocolError::InRoom),
+        Response::Error(ProtocolError::NotInRoom),
+        Response::Error(ProtocolError::NotInGame),
+        Response::Error(ProtocolError::BadDeck("too few".to_string())),
+        Response::Error(ProtocolError::Malformed("bad".to_string())),
+        Response::GameOver { outcome: game.outcome().cloned() },
+        Response::GameOver { outcome: None },
+        Response::Rooms(vec![]),
+        Response::Rooms(vec![room.clone(), room.clone()]),
+        Response::Room(room),
+        Response::Queued,
+    ];
+    responses.extend(game.view_events(1, 0).into_iter().map(Response::Event));
+    for response in responses {
+        round_trip(response);
+    }
+}
+
+#[test]
+fn concede_ends_game() {
+    let rules = rules();
+    let mut game = game(&rules);
+    assert_eq!(
+        game.handle(2, &Request::EndTurn),
+        Err(ProtocolError::Action(ActionError::NotYourTurn {
+            character: 1,
+            active: 0,
+        }))
+    );
+    assert_eq!(
+        game.handle(3, &Request::Pass),
+        Err(ProtocolError::NoSuchPlayer(3))
+    );
+    game.handle(2, &Request::Concede).unwrap();
+    assert_eq!(game.outcome(), Some(&Outcome {
+        winner: Some(0),
+        reason: EndReason::Conceded,
+    }));
+    assert!(game.recording.verify(&rules).is_ok());
+}
+
+#[test]
+fn partial_frames() {
+    let frame = protocol::encode(&Request::PlayCard { target: 1, index: 2 })
+        .unwrap();
+    for len in 0..frame.len() {
+        assert!(protocol::decode::<Request>(&frame[..len]).unwrap().is_none());
+    }
+
+    let mut buf = frame.clone();
+    buf.extend(protocol::encode(&Request::Pass).unwrap());
+    let (first, used) = protocol::decode::<Request>(&buf).unwrap().unwrap();
+    assert_eq!(first, Request::PlayCard { target: 1, index: 2 });
+    let (second, _) = protocol::decode::<Request>(&buf[used..]).unwrap().unwrap();
+    assert_eq!(second, Request::Pass);
+}
+
+#[test]
+fn bad_frames() {
+    let header = ((MAX_FRAME + 1) as u32).to_le_bytes();
+    assert!(matches!(
+        protocol::decode::<Request>(&header),
+        Err(FrameError::TooLarge(_))
+    ));
+
+    let mut frame = 4u32.to_le_bytes().to_vec();
+    frame.extend([0xff; 4]);
+    assert!(matches!(
+        protocol::decode::<Request>(&frame),
+        Err(FrameError::Malformed(_))
+    ));
+}
+
+#[test]
+fn error_variants_keep_their_positions() {
+    let errors = [
+        (ProtocolError::NoSuchPlayer(1), 5),
+        (ProtocolError::Malformed(String::new()), 6),
+        (ProtocolError::SeatTaken(1), 7),
+        (ProtocolError::BadDeck(String::new()), 14),
+        (ProtocolError::Unauthenticated, 15),
+    ];
+    for (error, position) in errors {
+        let body = bincode::serialize(&error).unwrap();
+        assert_eq!(body[..4], (position as u32).to_le_bytes(), "{:?}", error);
+    }
+}
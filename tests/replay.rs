@@ -0,0 +1,81 @@
+mod common;
+
+use common::*;
+use kier::replay::{checksum, REPLAY_VERSION};
+use kier::*;
+
+fn reactions() -> Rules {
+    let mut card = damage("jab", Targets::Enemy, -2);
+    card.reaction = true;
+    rules(vec![card])
+}
+
+fn played(rules: &Rules) -> Game {
+    let mut game = game(rules, 2, &[0; 10], 9);
+    for command in [
+        Command::PlayCard { character: 0, target: 1, index: 0 },
+        Command::EndTurn,
+        Command::PlayCard { character: 1, target: 0, index: 0 },
+        Command::Respond { character: 0, target: 1, index: 0 },
+        Command::Pass { character: 1 },
+        Command::PlayCard { character: 1, target: 1, index: 9 },
+    ] {
+        let _ = game.apply(command);
+    }
+    game
+}
+
+#[test]
+fn text_round_trip_verifies() {
+    let rules = reactions();
+    let game = played(&rules);
+    let text = game.recording.to_string();
+    assert!(text.starts_with(&format!("kier-replay {}\n", REPLAY_VERSION)));
+
+    let recording = Recording::parse(&text).unwrap();
+    assert_eq!(recording.steps, game.recording.steps);
+    assert_eq!(recording.to_string(), text);
+    let replayed = recording.verify(&rules).unwrap();
+    assert_eq!(checksum(&replayed.encounter), checksum(&game.encounter));
+}
+
+#[test]
+fn divergence_is_reported() {
+    let rules = reactions();
+    let mut recording = played(&rules).recording;
+    recording.steps[3].checksum ^= 1;
+    let divergence = recording.verify(&rules).err().unwrap();
+    assert_eq!(divergence.step, 3);
+    assert_eq!(divergence.found.checksum ^ 1, divergence.expected.checksum);
+
+    // Replaying under different rules diverges too.
+    let recording = played(&rules).recording;
+    assert!(recording.verify(&common::rules(vec![
+        damage("jab", Targets::Enemy, -3)
+    ])).is_err());
+}
+
+#[test]
+fn old_versions_are_refused() {
+    let rules = reactions();
+    let text = played(&rules).recording.to_string();
+    let old = text.replacen(
+        &format!("kier-replay {}", REPLAY_VERSION),
+        "kier-replay 1",
+        1
+    );
+    assert_eq!(Recording::parse(&old).err().unwrap().line, 1);
+}
+
+#[test]
+fn checksum_covers_the_window() {
+    let rules = reactions();
+    let mut game = game(&rules, 2, &[0; 10], 9);
+    game.apply(Command::EndTurn).unwrap();
+    game.apply(Command::PlayCard { character: 1, target: 0, index: 0 }).unwrap();
+    assert!(game.encounter.window().is_some());
+
+    let mut closed = game.encounter.clone();
+    closed.execute(Command::Pass { character: 0 }).unwrap();
+    assert_ne!(checksum(&closed), checksum(&game.encounter));
+}
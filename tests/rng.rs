@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+use kier::*;
+
+fn sequence(rng: &mut Rng, n: usize) -> Vec::<u64> {
+    (0..n).map(|_| rng.next_u64()).collect()
+}
+
+#[test]
+fn matches_splitmix64() {
+    let mut rng = Rng::new(0);
+    assert_eq!(
+        sequence(&mut rng, 3),
+        vec![0xe220_a839_7b1d_cdaf, 0x6e78_9e6a_a1b9_65f4, 0x06c4_5d18_8009_454f]
+    );
+}
+
+#[test]
+fn state_resumes_the_sequence() {
+    let mut rng = Rng::new(42);
+    sequence(&mut rng, 5);
+    let mut resumed = Rng::new(rng.state());
+    assert_eq!(sequence(&mut resumed, 10), sequence(&mut rng, 10));
+    assert_ne!(sequence(&mut Rng::new(1), 4), sequence(&mut Rng::new(2), 4));
+}
+
+#[test]
+fn below_stays_in_range() {
+    let mut rng = Rng::new(7);
+    assert_eq!(rng.below(0), 0);
+    assert_eq!(rng.below(1), 0);
+    let mut counts = [0usize; 6];
+    for _ in 0..6000 {
+        counts[rng.below(6) as usize] += 1;
+    }
+    assert!(counts.iter().all(|&n| (800..1200).contains(&n)), "{:?}", counts);
+    for _ in 0..100 {
+        assert!(rng.below(u64::MAX) < u64::MAX);
+    }
+}
+
+#[test]
+fn shuffles_are_seeded_permutations() {
+    let shuffled = |seed| {
+        let mut items: Vec::<u32> = (0..20).collect();
+        Rng::new(seed).shuffle(&mut items);
+        items
+    };
+    let mut sorted = shuffled(3);
+    assert_eq!(shuffled(3), sorted);
+    assert_ne!(shuffled(4), sorted);
+    sorted.sort();
+    assert_eq!(sorted, (0..20).collect::<Vec::<u32>>());
+
+    let mut empty: [u32; 0] = [];
+    Rng::new(3).shuffle(&mut empty);
+}
+
+#[test]
+fn decks_reshuffle_their_discards() {
+    let mut rng = Rng::new(9);
+    let mut deck = Deck::new(&[10, 11, 12]);
+    let mut drawn: Vec::<CardID> = (0..3)
+        .map(|_| deck.draw_card(&mut rng).unwrap())
+        .collect();
+    drawn.sort();
+    assert_eq!(drawn, vec![0, 1, 2]);
+    assert_eq!(deck.draw_card(&mut rng), None);
+
+    deck.discard_hand();
+    assert!(deck.draw_card(&mut rng).is_some());
+    assert_eq!((deck.deck.len(), deck.hand.len(), deck.discard.len()), (2, 1, 0));
+}
+
+#[test]
+fn encounters_deal_by_seed() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let cards: Vec::<CardID> = vec![0; 20];
+    let hand = |seed| {
+        let mut game = game(&rules, 2, &cards, seed);
+        game.apply(Command::EndTurn).unwrap();
+        game.apply(Command::EndTurn).unwrap();
+        game.encounter.characters[0].deck.hand.clone()
+    };
+    assert_eq!(hand(5), hand(5));
+    assert!((0..5).any(|seed| hand(seed) != hand(seed + 1)));
+}
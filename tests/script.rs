@@ -0,0 +1,270 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::script::{Script, ScriptError};
+use kier::*;
+
+fn fresh() -> Encounter {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    encounter(&rules, 2, &[0; 10])
+}
+
+// Runs `src` for character 0 against character 1.
+fn run(encounter: &mut Encounter, src: &str) -> Result<(), ScriptError> {
+    Script::compile(src).unwrap().run(encounter, 0, 1, None)
+}
+
+// The value a script leaves in character 0's health.
+fn eval(src: &str) -> TraitValue {
+    let mut encounter = fresh();
+    run(&mut encounter, src).unwrap();
+    encounter.get_trait(0, HEALTH)
+}
+
+fn compile_error(src: &str) -> (usize, String) {
+    match Script::compile(src) {
+        Err(ScriptError::Compile { line, message }) => (line, message),
+        other => panic!("{:?} compiled: {:?}", src, other),
+    }
+}
+
+#[test]
+fn arithmetic_and_control_flow() {
+    assert_eq!(eval("set_trait(player, 0, 2 + 3 * 4 - 10 / 3 % 2);"), 13);
+    assert_eq!(eval("set_trait(player, 0, -(1 - 8) + !0 + !5);"), 8);
+    assert_eq!(eval("set_trait(player, 0, (1 < 2) + (2 <= 2) + (3 > 4));"), 2);
+    assert_eq!(eval("
+        let total = 0;
+        for i in 1..5 { total = total + i; }
+        let n = 3;
+        while n > 0 { total = total * 2; n = n - 1; }
+        set_trait(player, 0, total);
+    "), 80);
+    assert_eq!(eval("
+        let x = 7;
+        if x == 1 { x = 10; } else if x != 7 { x = 20; } else { x = 30; }
+        set_trait(player, 0, x);
+    "), 30);
+
+    // Logic short-circuits and yields 0 or 1.
+    assert_eq!(eval("set_trait(player, 0, 0 && 1 / 0);"), 0);
+    assert_eq!(eval("set_trait(player, 0, 4 || 1 / 0);"), 1);
+    assert_eq!(eval("set_trait(player, 0, (2 && 3) + (0 || 9));"), 2);
+}
+
+#[test]
+fn scopes_shadow_and_end() {
+    assert_eq!(eval("
+        let x = 1;
+        if 1 { let x = 5; x = x + 1; }
+        set_trait(player, 0, x);
+    "), 1);
+    assert_eq!(
+        compile_error("if 1 { let y = 2; }\nset_trait(player, 0, y);"),
+        (2, "unknown variable 'y'".to_string())
+    );
+}
+
+#[test]
+fn predefined_variables() {
+    let script = Script::compile(
+        "set_trait(target, 0, player * 100 + target * 10 + feature);"
+    ).unwrap();
+    let mut encounter = fresh();
+    script.run(&mut encounter, 0, 1, None).unwrap();
+    assert_eq!(encounter.get_trait(1, HEALTH), 9);
+    script.run(&mut encounter, 1, 0, Some(4)).unwrap();
+    assert_eq!(encounter.get_trait(0, HEALTH), 104);
+}
+
+#[test]
+fn builtins() {
+    let mut encounter = fresh();
+    encounter.start();
+    run(&mut encounter, "
+        set_trait(player, 0, characters() * 1000 + features() * 100
+            + active() * 10 + round());
+        set_trait(target, 0, side(target) * 100 + hand(player) * 10
+            + hand(target));
+        set_trait(player, 1, deck(player) * 100 + discard_pile(player));
+    ").unwrap();
+    assert_eq!(encounter.get_trait(0, HEALTH), 2001);
+    assert_eq!(encounter.get_trait(1, HEALTH), 150);
+    assert_eq!(encounter.get_trait(0, ENERGY), 500);
+
+    run(&mut encounter, "
+        modify(target, 0, -7);
+        draw(target, 2);
+        discard(player, 3);
+        set_trait(player, 1, trait(target, 0));
+    ").unwrap();
+    assert_eq!(encounter.get_trait(1, HEALTH), 143);
+    assert_eq!(encounter.get_trait(0, ENERGY), 143);
+    assert_eq!(encounter.characters[1].deck.hand.len(), 2);
+    assert_eq!(encounter.characters[0].deck.hand.len(), 2);
+    assert_eq!(encounter.characters[0].deck.discard.len(), 3);
+
+    // Discarding more than the hand holds stops at an empty hand.
+    run(&mut encounter, "discard(player, 10);").unwrap();
+    assert!(encounter.characters[0].deck.hand.is_empty());
+
+    for _ in 0..20 {
+        run(&mut encounter, "set_trait(player, 0, random(3));").unwrap();
+        assert!((0..3).contains(&encounter.get_trait(0, HEALTH)));
+    }
+}
+
+#[test]
+fn feature_ids() {
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    rules.feature_list = Arc::new(vec![
+        Feature::new("altar", "", idle),
+        Feature::new("well", "", idle),
+    ]);
+    let characters = (0..2).map(|side| character(side, &[0; 10])).collect();
+    let mut encounter = Encounter::new(characters, vec![1, 0, 1], &rules, 1);
+    run(&mut encounter, "
+        set_trait(player, 0, features() * 100 + feature_id(0) * 10
+            + feature_id(1));
+    ").unwrap();
+    assert_eq!(encounter.get_trait(0, HEALTH), 310);
+    assert_eq!(
+        run(&mut encounter, "feature_id(3);"),
+        Err(ScriptError::BadArgument { builtin: "feature_id", value: 3 })
+    );
+}
+
+#[test]
+fn step_limit() {
+    let mut encounter = fresh();
+    assert_eq!(
+        run(&mut encounter, "while 1 { }"),
+        Err(ScriptError::StepLimit)
+    );
+
+    // Repeated draws and discards are charged one step each.
+    encounter.script_step_limit = 100;
+    assert_eq!(
+        run(&mut encounter, "draw(player, 1000000000);"),
+        Err(ScriptError::StepLimit)
+    );
+    assert!(encounter.characters[0].deck.hand.is_empty());
+    assert_eq!(
+        run(&mut encounter, "discard(player, 200);"),
+        Err(ScriptError::StepLimit)
+    );
+    assert!(run(&mut encounter, "draw(player, 5);").is_ok());
+    assert_eq!(encounter.characters[0].deck.hand.len(), 5);
+
+    // A loop runs until the limit, then stops where it was.
+    encounter.script_step_limit = 50;
+    assert_eq!(
+        run(&mut encounter, "for i in 0..1000 { modify(player, 0, -1); }"),
+        Err(ScriptError::StepLimit)
+    );
+    let health = encounter.get_trait(0, HEALTH);
+    assert!(health < 20 && health > 10, "{}", health);
+}
+
+#[test]
+fn nesting_limit() {
+    let nested = |depth: usize| format!(
+        "set_trait(player, 0, {}1{});",
+        "(".repeat(depth),
+        ")".repeat(depth)
+    );
+    assert_eq!(eval(&nested(40)), 1);
+    assert_eq!(
+        compile_error(&nested(100)).1,
+        "script nested too deeply"
+    );
+
+    assert!(Script::compile(&format!("{}1;", "-".repeat(40))).is_ok());
+    assert!(Script::compile(&format!("{}1;", "!".repeat(100))).is_err());
+
+    let blocks = |depth: usize| format!(
+        "{}{}",
+        "if 1 { ".repeat(depth),
+        "}".repeat(depth)
+    );
+    assert!(Script::compile(&blocks(40)).is_ok());
+    assert_eq!(compile_error(&blocks(100)).1, "script nested too deeply");
+    assert!(Script::compile(
+        &format!("{}{{ }}", "if 0 { } else ".repeat(100))
+    ).is_err());
+}
+
+#[test]
+fn compile_errors() {
+    let cases = [
+        ("x;", 1, "unknown variable 'x'"),
+        ("\nfoo(1);", 2, "unknown function 'foo'"),
+        ("trait(player);", 1, "trait takes 2 arguments, found 1"),
+        ("characters(1);", 1, "characters takes 0 arguments, found 1"),
+        ("player = 3;", 1, "cannot assign to 'player'"),
+        ("feature = 3;", 1, "cannot assign to 'feature'"),
+        ("y = 3;", 1, "unknown variable 'y'"),
+        ("let if = 1;", 1, "expected a name"),
+        ("let x = 1", 1, "expected ';'"),
+        ("let x 1;", 1, "expected '='"),
+        ("for i 0..2 { }", 1, "expected 'in'"),
+        ("for i in 0 { }", 1, "expected '..'"),
+        ("if 1 {\n1;\n", 2, "expected '}'"),
+        ("while 1 1;", 1, "expected '{'"),
+        ("(1;", 1, "expected ')'"),
+        ("1 # 2;", 1, "unexpected character '#'"),
+        ("\n\n99999999999999999999;", 3, "number too large: 99999999999999999999"),
+    ];
+    for (src, line, message) in cases {
+        assert_eq!(compile_error(src), (line, message.to_string()), "{:?}", src);
+    }
+
+    let error = Script::compile("\nbad(1);").unwrap_err();
+    assert_eq!(error.to_string(), "line 2: unknown function 'bad'");
+    assert!(Script::compile("// nothing but a comment").is_ok());
+}
+
+#[test]
+fn runtime_errors() {
+    let mut encounter = fresh();
+    let cases = [
+        ("1 / 0;", ScriptError::DivideByZero),
+        ("let z = 0; 5 % z;", ScriptError::DivideByZero),
+        ("trait(2, 0);", ScriptError::BadArgument { builtin: "trait", value: 2 }),
+        ("trait(0, -1);", ScriptError::BadArgument { builtin: "trait", value: -1 }),
+        ("side(-1);", ScriptError::BadArgument { builtin: "side", value: -1 }),
+        ("hand(9);", ScriptError::BadArgument { builtin: "hand", value: 9 }),
+        ("random(-3);", ScriptError::BadArgument { builtin: "random", value: -3 }),
+        ("draw(0, -1);", ScriptError::BadArgument { builtin: "draw", value: -1 }),
+        ("modify(5, 0, 1);", ScriptError::BadArgument { builtin: "modify", value: 5 }),
+        ("feature_id(0);", ScriptError::BadArgument { builtin: "feature_id", value: 0 }),
+    ];
+    for (src, error) in cases {
+        assert_eq!(run(&mut encounter, src), Err(error), "{:?}", src);
+    }
+    assert_eq!(
+        ScriptError::BadArgument { builtin: "side", value: -1 }.to_string(),
+        "bad argument -1 to side"
+    );
+}
+
+#[test]
+fn failed_card_scripts_are_reported() {
+    let mut card = damage("fizzle", Targets::Enemy, -2);
+    card.effects.push(Effect::Script(Arc::new(
+        Script::compile("modify(target, 0, -1); 1 / 0; modify(target, 0, -50);")
+            .unwrap()
+    )));
+    let rules = rules(vec![card]);
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    encounter.start();
+    encounter.play_card(0, 1, 0).unwrap();
+
+    // The script stops at the error, but the card still resolves.
+    assert_eq!(encounter.get_trait(1, HEALTH), 17);
+    assert!(encounter.events().iter().any(|event| *event == Event::ScriptFailed {
+        message: "division by zero".to_string(),
+    }));
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::*;
+use kier::sim::Simulation;
+use kier::*;
+
+fn simulation(games: usize, seed: u64) -> Simulation {
+    let mut players = players(2, &[0; 10]);
+    players[1].character = character(1, &[1; 10]);
+    Simulation::new(players, games, seed)
+}
+
+fn duel() -> Rules {
+    rules(vec![
+        damage("strike", Targets::Enemy, -6),
+        damage("scratch", Targets::Enemy, -1),
+    ])
+}
+
+// Side 4 has no characters, but is handed the game in round 2.
+fn awarded(encounter: &Encounter) -> Option<Outcome> {
+    (encounter.round >= 2).then(|| Outcome {
+        winner: Some(4),
+        reason: EndReason::Custom("awarded".to_string()),
+    })
+}
+
+#[test]
+fn same_seed_same_report() {
+    let rules = duel();
+    let csv = |seed| {
+        let mut out = Vec::new();
+        simulation(8, seed).run(&rules).write_csv(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(csv(3), csv(3));
+    assert!(csv(3).starts_with("side,wins,win_rate\n0,"));
+}
+
+#[test]
+fn stronger_deck_wins() {
+    let report = simulation(6, 1).run(&duel());
+    assert_eq!(report.games, 6);
+    assert_eq!(report.wins, vec![6, 0]);
+    assert_eq!(report.win_rates, vec![1.0, 0.0]);
+    assert_eq!(report.cards[0].games, 6);
+    assert_eq!(report.cards[0].win_rate, 1.0);
+    assert!(report.cards[0].correlation > 0.9);
+    assert_eq!(report.cards[1].win_rate, 0.0);
+}
+
+#[test]
+fn unknown_winning_sides() {
+    let mut rules = duel();
+    rules.end_conditions = vec![EndCondition::Custom(awarded)];
+    for games in [1, 3] {
+        let mut sim = simulation(games, 1);
+        sim.max_rounds = 3;
+        let report = sim.run(&rules);
+        assert_eq!(report.wins, vec![0, 0]);
+        assert_eq!(report.other_wins, games);
+        assert_eq!(report.draws + report.unfinished, 0);
+        assert_eq!(report.cards[0].games, games as u64);
+        for card in report.cards.iter() {
+            assert_eq!(card.win_rate, 0.0);
+            assert_eq!(card.correlation, 0.0);
+        }
+    }
+}
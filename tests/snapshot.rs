@@ -0,0 +1,170 @@
+mod common;
+
+use common::*;
+use kier::snapshot::{SnapshotError, SNAPSHOT_VERSION};
+use kier::*;
+
+fn saved(game: &Game) -> String {
+    serde_json::to_string(&game.snapshot()).unwrap()
+}
+
+fn load(json: &str, rules: &Rules) -> Result<Game, SnapshotError> {
+    Game::from_snapshot(serde_json::from_str(json).unwrap(), rules)
+}
+
+#[test]
+fn round_trip() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -7)]);
+    let mut game = game(&rules, 2, &[0; 10], 3);
+    game.apply(Command::PlayCard { character: 0, target: 1, index: 0 }).unwrap();
+    game.apply(Command::EndTurn).unwrap();
+
+    let json = saved(&game);
+    let mut loaded = load(&json, &rules).unwrap();
+    assert_eq!(saved(&loaded), json);
+    assert_eq!(loaded.view(1), game.view(1));
+
+    // Both copies carry on identically, up to the end of the encounter.
+    for _ in 0..3 {
+        let command = Command::PlayCard { character: 1, target: 0, index: 0 };
+        assert_eq!(loaded.apply(command), game.apply(command));
+    }
+    assert!(loaded.encounter.done);
+    assert_eq!(saved(&loaded), saved(&game));
+    assert!(loaded.recording.verify(&rules).is_ok());
+}
+
+#[test]
+fn limits_are_kept() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut game = game(&rules, 2, &[0; 10], 3);
+    game.encounter.script_step_limit = 50;
+    game.encounter.trigger_limit = 4;
+    let loaded = load(&saved(&game), &rules).unwrap();
+    assert_eq!(loaded.encounter.script_step_limit, 50);
+    assert_eq!(loaded.encounter.trigger_limit, 4);
+}
+
+#[test]
+fn unknown_versions_are_refused() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let game = game(&rules, 2, &[0; 10], 3);
+    for version in [0, SNAPSHOT_VERSION + 1] {
+        let mut snapshot = game.snapshot();
+        snapshot.version = version;
+        assert_eq!(
+            Game::from_snapshot(snapshot, &rules).err(),
+            Some(SnapshotError::UnsupportedVersion(version))
+        );
+    }
+}
+
+// Saved by version 1 after one hit and an ended turn, then ended by
+// hand.
+const VERSION_1: &str = include_str!("data/snapshot-v1.json");
+
+#[test]
+fn version_1_saves_load() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -4)]);
+    let game = load(VERSION_1, &rules).unwrap();
+    assert!(game.encounter.done);
+    assert_eq!(game.outcome(), None);
+    assert_eq!(
+        game.encounter.events().last(),
+        Some(&Event::EncounterEnded { outcome: None })
+    );
+    assert_eq!((game.encounter.active, game.encounter.round), (1, 1));
+    assert_eq!(game.encounter.get_trait(1, HEALTH), 16);
+    assert_eq!(game.recording.steps.len(), 2);
+    assert!(game.winners().is_empty());
+    assert_eq!(game.encounter.script_step_limit, 10_000);
+    assert_eq!(game.encounter.trigger_limit, 100);
+
+    // Saving again writes the current version.
+    let json = saved(&game);
+    assert!(json.starts_with(&format!("{{\"version\":{},", SNAPSHOT_VERSION)));
+    assert_eq!(saved(&load(&json, &rules).unwrap()), json);
+}
+
+// A game paused with a card on the stack and a response window open.
+fn pending(rules: &Rules) -> Game {
+    let mut game = game(rules, 2, &[0; 10], 5);
+    game.apply(Command::EndTurn).unwrap();
+    game.apply(Command::PlayCard { character: 1, target: 0, index: 0 }).unwrap();
+    assert_eq!(game.encounter.stack().len(), 1);
+    assert!(game.encounter.window().is_some());
+    game
+}
+
+fn reactions() -> Rules {
+    let mut card = damage("jab", Targets::Enemy, -2);
+    card.reaction = true;
+    rules(vec![card])
+}
+
+#[test]
+fn round_trip_mid_stack() {
+    let rules = reactions();
+    let mut game = pending(&rules);
+    let json = saved(&game);
+    let mut loaded = load(&json, &rules).unwrap();
+    assert_eq!(saved(&loaded), json);
+
+    for command in [
+        Command::Respond { character: 0, target: 1, index: 0 },
+        Command::Pass { character: 1 },
+    ] {
+        game.apply(command).unwrap();
+        loaded.apply(command).unwrap();
+    }
+    assert!(loaded.encounter.stack().is_empty());
+    assert_eq!(saved(&loaded), saved(&game));
+}
+
+#[test]
+fn bad_indices_are_refused() {
+    let rules = reactions();
+    let game = pending(&rules);
+    let refused = |corrupt: fn(&mut Snapshot)| {
+        let mut snapshot = game.snapshot();
+        corrupt(&mut snapshot);
+        Game::from_snapshot(snapshot, &rules).err()
+    };
+
+    assert_eq!(
+        refused(|s| s.encounter.active = 2),
+        Some(SnapshotError::NoSuchCharacter(2))
+    );
+    assert_eq!(
+        refused(|s| s.players[1].1 = 7),
+        Some(SnapshotError::NoSuchCharacter(7))
+    );
+    assert_eq!(
+        refused(|s| s.encounter.characters[1].deck.hand[0] = 10),
+        Some(SnapshotError::NoSuchSlot { character: 1, slot: 10 })
+    );
+    assert_eq!(
+        refused(|s| s.encounter.characters[0].deck.discard.push(99)),
+        Some(SnapshotError::NoSuchSlot { character: 0, slot: 99 })
+    );
+    assert_eq!(
+        refused(|s| s.encounter.destroyed.push(0)),
+        Some(SnapshotError::NoSuchFeature(0))
+    );
+    assert_eq!(
+        refused(|s| s.encounter.window.as_mut().unwrap().waiting.clear()),
+        Some(SnapshotError::EmptyWindow)
+    );
+    assert_eq!(
+        refused(|s| s.encounter.window.as_mut().unwrap().waiting[0] = 3),
+        Some(SnapshotError::NoSuchCharacter(3))
+    );
+    assert_eq!(
+        refused(|s| match &mut s.encounter.stack[0] {
+            StackEntry::Card { targets, .. } => targets[0] = 4,
+            StackEntry::Feature { .. } => unreachable!(),
+        }),
+        Some(SnapshotError::NoSuchCharacter(4))
+    );
+    assert!(refused(|_| ()).is_none());
+}
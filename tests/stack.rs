@@ -0,0 +1,176 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::effect::Who;
+use kier::*;
+
+// Card 0 strikes for 5, card 1 is a reaction jabbing for 1.
+fn stack_rules(features: Vec::<Feature>) -> Rules {
+    let mut jab = damage("jab", Targets::Enemy, -1);
+    jab.reaction = true;
+    let mut rules = rules(vec![damage("strike", Targets::Enemy, -5), jab]);
+    rules.feature_list = Arc::new(features);
+    rules
+}
+
+fn encounter(rules: &Rules, features: Vec::<FeatureID>) -> Encounter {
+    let characters = vec![character(0, &[0; 10]), character(1, &[1; 10])];
+    let mut encounter = Encounter::new(characters, features, rules, 1);
+    encounter.start();
+    encounter.draw_card(1);
+    encounter
+}
+
+fn health(encounter: &Encounter) -> Vec::<TraitValue> {
+    vec![encounter.get_trait(0, HEALTH), encounter.get_trait(1, HEALTH)]
+}
+
+fn changed(encounter: &Encounter) -> Vec::<CharacterIdx> {
+    encounter.events().iter()
+        .filter_map(|event| match *event {
+            Event::TraitChanged { character, .. } => Some(character),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn resolves_last_in_first_out() {
+    let rules = stack_rules(Vec::new());
+    let mut enc = encounter(&rules, Vec::new());
+    enc.play_card(0, 1, 0).unwrap();
+    assert_eq!(enc.window().unwrap().waiting, vec![1]);
+    assert_eq!(enc.actor(), 1);
+    assert_eq!(enc.end_turn(), Err(ActionError::WindowOpen));
+    assert_eq!(
+        enc.pass(0),
+        Err(ActionError::NoPriority { character: 0, priority: 1 })
+    );
+
+    enc.respond(1, 0, 0).unwrap();
+    // Character 0 holds no reactions, so the stack resolves at once.
+    assert!(enc.window().is_none());
+    assert!(enc.stack().is_empty());
+    assert_eq!(health(&enc), vec![19, 15]);
+    assert_eq!(changed(&enc), vec![0, 1]);
+    assert_eq!(enc.pass(1), Err(ActionError::NoWindow));
+}
+
+#[test]
+fn passing_resolves_the_stack() {
+    let rules = stack_rules(Vec::new());
+    let mut enc = encounter(&rules, Vec::new());
+    enc.play_card(0, 1, 0).unwrap();
+    assert_eq!(health(&enc), vec![20, 20]);
+    enc.pass(1).unwrap();
+    assert_eq!(health(&enc), vec![20, 15]);
+    assert!(enc.events().contains(&Event::Passed { character: 1 }));
+}
+
+#[test]
+fn reaction_features_keep_to_their_side() {
+    let mut shield = Feature::new("shield", "", idle);
+    shield.reaction = true;
+    shield.side = Some(1);
+    shield.effects.push(Effect::Counter);
+    let rules = stack_rules(vec![shield]);
+
+    let characters = vec![character(0, &[0; 10]), character(1, &[0; 10])];
+    let mut enc = Encounter::new(characters, vec![0], &rules, 1);
+    enc.start();
+    enc.play_card(0, 1, 0).unwrap();
+    assert_eq!(enc.window().unwrap().waiting, vec![1]);
+    assert_eq!(enc.legal_moves(), vec![
+        Command::RespondFeature { character: 1, feature: 0 },
+        Command::Pass { character: 1 },
+    ]);
+
+    enc.respond_feature(1, 0).unwrap();
+    assert!(enc.stack().is_empty());
+    assert_eq!(health(&enc), vec![20, 20]);
+    assert!(enc.events().contains(&Event::Countered { character: 0 }));
+
+    // Character 1 strikes back; the shield does not answer for side 0.
+    enc.end_turn().unwrap();
+    enc.play_card(1, 0, 0).unwrap();
+    assert!(enc.window().is_none());
+    assert_eq!(health(&enc), vec![15, 20]);
+}
+
+#[test]
+fn reaction_features_wait_on_the_stack_once() {
+    let mut rally = Feature::new("rally", "", idle);
+    rally.reaction = true;
+    rally.effects.push(Effect::ModifyTrait { who: Who::Player, id: HEALTH, by: 1 });
+    let rules = stack_rules(vec![rally]);
+
+    let characters = vec![character(0, &[0; 10]), character(1, &[0; 10])];
+    let mut enc = Encounter::new(characters, vec![0], &rules, 1);
+    enc.start();
+    enc.play_card(0, 1, 0).unwrap();
+    enc.respond_feature(1, 0).unwrap();
+
+    // Nobody may use the rally again while it waits, so the window
+    // closes instead of bouncing between the two characters.
+    assert!(enc.window().is_none());
+    assert_eq!(health(&enc), vec![20, 16]);
+
+    enc.play_card(0, 1, 0).unwrap();
+    assert_eq!(enc.window().unwrap().waiting, vec![1]);
+    assert_eq!(enc.respond_feature(0, 0), Err(ActionError::NoPriority {
+        character: 0,
+        priority: 1,
+    }));
+    enc.respond_feature(1, 0).unwrap();
+    assert_eq!(health(&enc), vec![20, 12]);
+}
+
+#[test]
+fn pending_reactions_are_refused() {
+    let mut rally = Feature::new("rally", "", idle);
+    rally.reaction = true;
+    let rules = stack_rules(vec![rally]);
+
+    let characters = vec![character(0, &[1; 10]), character(1, &[0; 10])];
+    let mut enc = Encounter::new(characters, vec![0], &rules, 1);
+    enc.start();
+    enc.play_card(0, 1, 0).unwrap();
+    enc.respond_feature(1, 0).unwrap();
+    assert_eq!(enc.window().unwrap().waiting, vec![0]);
+    assert_eq!(
+        enc.respond_feature(0, 0),
+        Err(ActionError::ReactionPending(0))
+    );
+    assert!(!enc.legal_moves().iter().any(|command| matches!(
+        command,
+        Command::RespondFeature { .. }
+    )));
+}
+
+#[test]
+fn conceding_waits_for_the_stack() {
+    let rules = stack_rules(Vec::new());
+    let characters = vec![
+        character(0, &[0; 10]),
+        character(1, &[1; 10]),
+        character(2, &[1; 10]),
+    ];
+    let mut enc = Encounter::new(characters, Vec::new(), &rules, 1);
+    enc.start();
+    enc.draw_card(1);
+    enc.draw_card(2);
+    enc.play_card(0, 1, 0).unwrap();
+    assert_eq!(enc.window().unwrap().waiting, vec![1, 2]);
+
+    // The strike still resolves, and only then is the turn handed on.
+    enc.concede(0).unwrap();
+    assert_eq!(enc.actor(), 1);
+    enc.pass(1).unwrap();
+    enc.pass(2).unwrap();
+    assert!(enc.stack().is_empty());
+    assert_eq!(enc.get_trait(1, HEALTH), 15);
+    assert_eq!(enc.active, 1);
+    assert!(!enc.done);
+}
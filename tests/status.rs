@@ -0,0 +1,127 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+const POISON: u64 = 0;
+const STRENGTH: u64 = 1;
+const STUN: u64 = 2;
+
+fn poison(encounter: &mut Encounter, cid: CharacterIdx, stacks: u32) {
+    encounter.modify_trait(cid, HEALTH, -(stacks as TraitValue));
+}
+
+fn with_statuses() -> Rules {
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut poisoned = Status::new("poison", "");
+    poisoned.on_tick = Some(poison);
+    let mut strength = Status::new("strength", "");
+    strength.modifiers.push((ENERGY, 2));
+    let mut stun = Status::new("stun", "");
+    stun.skip_turn = true;
+    rules.status_list = Arc::new(vec![poisoned, strength, stun]);
+    rules
+}
+
+fn started(rules: &Rules) -> Encounter {
+    let mut encounter = encounter(rules, 2, &[0; 10]);
+    encounter.start();
+    encounter
+}
+
+#[test]
+fn stacks_merge() {
+    let rules = with_statuses();
+    let mut encounter = started(&rules);
+    assert!(!encounter.apply_status(0, POISON, 0, None));
+    assert!(!encounter.apply_status(5, POISON, 1, None));
+
+    encounter.apply_status(1, POISON, 2, Some(1));
+    encounter.apply_status(1, POISON, 1, Some(3));
+    assert_eq!(encounter.statuses(1), &[
+        StatusEffect { id: POISON, stacks: 3, turns: Some(3) },
+    ]);
+    encounter.apply_status(1, POISON, 1, None);
+    assert_eq!(encounter.statuses(1)[0].turns, None);
+    assert_eq!(encounter.status_stacks(1, POISON), 4);
+    assert_eq!(encounter.status_stacks(1, STRENGTH), 0);
+}
+
+#[test]
+fn modifiers_scale_with_stacks() {
+    let rules = with_statuses();
+    let mut encounter = started(&rules);
+    encounter.apply_status(0, STRENGTH, 2, None);
+    assert_eq!(encounter.get_trait(0, ENERGY), 7);
+    assert_eq!(encounter.base_trait(0, ENERGY), 3);
+    assert!(encounter.events().contains(&Event::TraitChanged {
+        character: 0,
+        id: ENERGY,
+        old: Some(3),
+        new: 7,
+    }));
+
+    assert!(encounter.remove_status(0, STRENGTH));
+    assert!(!encounter.remove_status(0, STRENGTH));
+    assert_eq!(encounter.get_trait(0, ENERGY), 3);
+    assert_eq!(encounter.events().last(), Some(&Event::TraitChanged {
+        character: 0,
+        id: ENERGY,
+        old: Some(7),
+        new: 3,
+    }));
+}
+
+#[test]
+fn ticks_and_expires_on_the_holders_turns() {
+    let rules = with_statuses();
+    let mut encounter = started(&rules);
+    encounter.apply_status(1, POISON, 3, Some(2));
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.get_trait(1, HEALTH), 17);
+
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.statuses(1)[0].turns, Some(1));
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.get_trait(1, HEALTH), 14);
+    assert_eq!(encounter.get_trait(0, HEALTH), 20);
+
+    encounter.end_turn().unwrap();
+    assert!(encounter.statuses(1).is_empty());
+    assert!(encounter.events().contains(&Event::StatusRemoved {
+        character: 1,
+        id: POISON,
+    }));
+    encounter.end_turn().unwrap();
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.get_trait(1, HEALTH), 14);
+}
+
+#[test]
+fn stunned_characters_lose_their_turns() {
+    let rules = with_statuses();
+    let mut encounter = started(&rules);
+    encounter.apply_status(1, STUN, 1, Some(1));
+    encounter.end_turn().unwrap();
+    assert_eq!((encounter.active, encounter.round), (0, 2));
+    assert!(encounter.characters[1].deck.hand.is_empty());
+    assert!(encounter.statuses(1).is_empty());
+
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.active, 1);
+}
+
+#[test]
+fn everyone_stunned_passes_a_round() {
+    let rules = with_statuses();
+    let mut encounter = started(&rules);
+    encounter.apply_status(0, STUN, 1, None);
+    encounter.apply_status(1, STUN, 1, None);
+    encounter.end_turn().unwrap();
+
+    // Both lose a turn, then the one whose turn was due plays anyway.
+    assert_eq!((encounter.active, encounter.round), (1, 2));
+    assert_eq!(encounter.phase, Phase::Main);
+}
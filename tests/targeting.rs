@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::script::Script;
+use kier::*;
+
+// Characters 0 and 1 are allies, 2 and 3 their enemies.
+fn teams(rules: &Rules) -> Encounter {
+    let characters = [0, 0, 1, 1]
+        .iter()
+        .map(|&side| character(side, &[0; 10]))
+        .collect();
+    let mut encounter = Encounter::new(characters, Vec::new(), rules, 1);
+    encounter.start();
+    encounter
+}
+
+fn health(encounter: &Encounter) -> Vec::<TraitValue> {
+    (0..encounter.characters.len())
+        .map(|cid| encounter.get_trait(cid, HEALTH))
+        .collect()
+}
+
+#[test]
+fn legal_targets_by_kind() {
+    let cases = [
+        (Targets::None, vec![]),
+        (Targets::Own, vec![0]),
+        (Targets::Ally, vec![1]),
+        (Targets::Enemy, vec![2, 3]),
+        (Targets::Any, vec![0, 1, 2, 3]),
+        (Targets::AllEnemies, vec![2, 3]),
+    ];
+    for (targets, expected) in cases {
+        let rules = rules(vec![damage("hit", targets, -1)]);
+        let encounter = teams(&rules);
+        assert_eq!(encounter.legal_targets(0, 0), expected, "{:?}", targets);
+    }
+}
+
+#[test]
+fn rejects_illegal_targets() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -5)]);
+    let mut encounter = teams(&rules);
+    assert_eq!(
+        encounter.play_card(0, 1, 0),
+        Err(ActionError::IllegalTarget { target: 1, targets: Targets::Enemy })
+    );
+    assert!(encounter.play_card(0, 9, 0).is_err());
+    assert_eq!(health(&encounter), vec![20; 4]);
+
+    encounter.play_card(0, 3, 0).unwrap();
+    assert_eq!(health(&encounter), vec![20, 20, 20, 15]);
+}
+
+#[test]
+fn all_enemies_hits_each_enemy() {
+    let rules = rules(vec![damage("storm", Targets::AllEnemies, -4)]);
+    let mut encounter = teams(&rules);
+    encounter.play_card(0, 2, 0).unwrap();
+    assert_eq!(health(&encounter), vec![20, 20, 16, 16]);
+}
+
+#[test]
+fn feature_cards_do_not_hit_characters() {
+    let mut card = damage("smash", Targets::Feature, -3);
+    card.effects.push(Effect::Script(Arc::new(
+        Script::compile("modify(player, 0, feature * 100);").unwrap()
+    )));
+    let mut rules = rules(vec![card]);
+    rules.feature_list = Arc::new(vec![
+        Feature::new("altar", "", idle),
+        Feature::new("well", "", idle),
+    ]);
+
+    let characters = (0..2).map(|side| character(side, &[0; 10])).collect();
+    let mut encounter = Encounter::new(characters, vec![0, 1], &rules, 1);
+    encounter.start();
+    assert_eq!(encounter.legal_targets(0, 0), vec![0, 1]);
+    encounter.play_card(0, 1, 0).unwrap();
+
+    // The player stands in as the target and the script sees feature 1.
+    assert_eq!(health(&encounter), vec![117, 20]);
+    assert!(encounter.events().iter().any(|event| matches!(
+        event,
+        Event::CardPlayed { character: 0, target: 1, .. }
+    )));
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+const CONSTITUTION: TraitID = 2;
+const MAX_HEALTH: TraitID = 3;
+
+fn max_health(encounter: &Encounter, cid: CharacterIdx) -> TraitValue {
+    encounter.get_trait(cid, CONSTITUTION) * 10
+}
+
+// Health and energy, plus constitution and a max health derived from
+// it.
+fn derived() -> Rules {
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut list = traits();
+    let mut constitution = Trait::new("constitution");
+    constitution.min = 1;
+    constitution.max = 5;
+    constitution.default = 2;
+    let mut max = Trait::new("max health");
+    max.derive = Some(max_health);
+    list.push(constitution);
+    list.push(max);
+    rules.trait_list = Arc::new(list);
+    rules
+}
+
+#[test]
+fn defaults_and_bounds() {
+    let rules = derived();
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    assert_eq!(encounter.get_trait(0, HEALTH), 20);
+    assert_eq!(encounter.get_trait(0, CONSTITUTION), 2);
+    assert_eq!(encounter.get_trait(0, 40), 0);
+
+    assert!(encounter.set_trait(0, CONSTITUTION, 9));
+    assert_eq!(encounter.get_trait(0, CONSTITUTION), 5);
+    assert!(encounter.modify_trait(0, CONSTITUTION, -100));
+    assert_eq!(encounter.get_trait(0, CONSTITUTION), 1);
+    assert!(encounter.modify_trait(0, ENERGY, TraitValue::MIN));
+    assert_eq!(encounter.get_trait(0, ENERGY), 0);
+    assert!(encounter.modify_trait(0, HEALTH, TraitValue::MAX));
+    assert_eq!(encounter.get_trait(0, HEALTH), TraitValue::MAX);
+
+    // Undefined traits are stored as they are.
+    assert!(encounter.set_trait(1, 40, -7));
+    assert_eq!(encounter.get_trait(1, 40), -7);
+    assert!(!encounter.set_trait(2, HEALTH, 1));
+}
+
+#[test]
+fn starting_values_are_clamped() {
+    let rules = derived();
+    let traits = HashMap::from([(CONSTITUTION, 50), (ENERGY, -3)]);
+    let characters = vec![
+        Character::new(traits, Deck::new(&[0; 10])),
+        character(1, &[0; 10]),
+    ];
+    let encounter = Encounter::new(characters, Vec::new(), &rules, 1);
+    assert_eq!(encounter.characters[0].traits().get(&CONSTITUTION), Some(&5));
+    assert_eq!(encounter.characters[0].traits().get(&ENERGY), Some(&0));
+}
+
+#[test]
+fn derived_traits_follow_their_sources() {
+    let rules = derived();
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    assert_eq!(encounter.get_trait(0, MAX_HEALTH), 20);
+
+    // Derived traits cannot be written, and report their changes.
+    assert!(!encounter.set_trait(0, MAX_HEALTH, 100));
+    assert!(!encounter.modify_trait(0, MAX_HEALTH, 1));
+    assert!(encounter.events().is_empty());
+
+    assert!(encounter.set_trait(0, CONSTITUTION, 4));
+    assert_eq!(encounter.get_trait(0, MAX_HEALTH), 40);
+    assert_eq!(encounter.events(), &[
+        Event::TraitChanged { character: 0, id: CONSTITUTION, old: Some(2), new: 4 },
+        Event::TraitChanged { character: 0, id: MAX_HEALTH, old: Some(20), new: 40 },
+    ]);
+    assert!(!encounter.characters[0].traits().contains_key(&MAX_HEALTH));
+}
+
+#[test]
+fn refills_respect_bounds() {
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut list = traits();
+    list[ENERGY as usize].max = 4;
+    list[ENERGY as usize].refill = Some(Refill::Add(3));
+    let mut mana = Trait::new("mana");
+    mana.refill = Some(Refill::ToTrait(ENERGY));
+    list.push(mana);
+    const MANA: TraitID = 2;
+    rules.trait_list = Arc::new(list);
+
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    encounter.start();
+    assert_eq!(encounter.get_trait(0, ENERGY), 4);
+    assert_eq!(encounter.get_trait(0, MANA), 4);
+    encounter.set_trait(0, ENERGY, 0);
+    encounter.end_turn().unwrap();
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.get_trait(0, ENERGY), 3);
+    assert_eq!(encounter.get_trait(0, MANA), 3);
+}
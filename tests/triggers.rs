@@ -0,0 +1,146 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::effect::{self, Who};
+use kier::*;
+
+const MARK: TraitID = 5;
+
+// A feature that changes the health of each event's subject by `by`.
+fn watcher(trigger: Trigger, by: TraitValue) -> Feature {
+    let mut feature = Feature::new("watcher", "", idle);
+    feature.triggers.push(trigger);
+    feature.effects.push(Effect::ModifyTrait { who: Who::Target, id: HEALTH, by });
+    feature
+}
+
+fn with_features(cards: Vec::<Card>, features: Vec::<Feature>) -> Encounter {
+    let mut rules = rules(cards);
+    let ids = (0..features.len() as FeatureID).collect();
+    rules.feature_list = Arc::new(features);
+    let characters = (0..2).map(|side| character(side, &[0; 10])).collect();
+    let mut encounter = Encounter::new(characters, ids, &rules, 1);
+    encounter.start();
+    encounter
+}
+
+fn health(encounter: &Encounter) -> Vec::<TraitValue> {
+    (0..2).map(|cid| encounter.get_trait(cid, HEALTH)).collect()
+}
+
+fn hit() -> Vec::<Card> {
+    vec![damage("hit", Targets::Enemy, -3)]
+}
+
+#[test]
+fn turn_triggers_hit_whoever_is_up() {
+    let mut encounter = with_features(hit(), vec![
+        watcher(Trigger::TurnStarted, -1),
+        watcher(Trigger::TurnEnded, -10),
+    ]);
+    assert_eq!(health(&encounter), vec![19, 20]);
+    encounter.end_turn().unwrap();
+    assert_eq!(health(&encounter), vec![9, 19]);
+    assert!(encounter.events().contains(&Event::FeatureTriggered {
+        feature: 1,
+        id: 1,
+    }));
+}
+
+#[test]
+fn card_played_triggers_hit_the_player() {
+    let mut encounter = with_features(hit(), vec![
+        watcher(Trigger::CardPlayed, -2),
+    ]);
+    encounter.play_card(0, 1, 0).unwrap();
+    assert_eq!(health(&encounter), vec![18, 17]);
+}
+
+#[test]
+fn thresholds_fire_once_per_crossing() {
+    let below = Trigger::TraitBelow { id: HEALTH, threshold: 15 };
+    let mut feature = watcher(below, 0);
+    feature.effects = vec![Effect::ModifyTrait { who: Who::Target, id: MARK, by: 1 }];
+    let mut encounter = with_features(hit(), vec![feature]);
+    let marks = |encounter: &Encounter| encounter.get_trait(1, MARK);
+
+    encounter.set_trait(1, HEALTH, 15);
+    assert_eq!(marks(&encounter), 0);
+    for _ in 0..2 {
+        encounter.play_card(0, 1, 0).unwrap();
+    }
+    assert_eq!(marks(&encounter), 1);
+
+    encounter.set_trait(1, HEALTH, 16);
+    encounter.play_card(0, 1, 0).unwrap();
+    assert_eq!(marks(&encounter), 2);
+}
+
+#[test]
+fn defeat_triggers_can_save_the_day() {
+    let mut phoenix = watcher(Trigger::Defeated, 0);
+    phoenix.effects = vec![
+        Effect::SetTrait { who: Who::Target, id: HEALTH, value: 5 },
+    ];
+    let mut encounter = with_features(
+        vec![damage("smite", Targets::Enemy, -30)],
+        vec![phoenix]
+    );
+    encounter.play_card(0, 1, 0).unwrap();
+    assert!(!encounter.done);
+    assert_eq!(health(&encounter), vec![20, 5]);
+
+    // Traits other than the defeat trait never count as a defeat.
+    encounter.set_trait(1, ENERGY, 0);
+    assert_eq!(health(&encounter), vec![20, 5]);
+}
+
+#[test]
+fn cards_trigger_from_the_hand() {
+    let mut thorns = Card::new("thorns", "", effect::no_effect);
+    thorns.triggers.push(Trigger::CardPlayed);
+    thorns.effects.push(Effect::ModifyTrait { who: Who::Target, id: HEALTH, by: -1 });
+    let mut encounter = with_features(vec![thorns], Vec::new());
+
+    // The played thorns hits its target, and the four left in hand fire
+    // back at the player.
+    encounter.play_card(0, 1, 0).unwrap();
+    assert_eq!(health(&encounter), vec![16, 19]);
+    let triggered = encounter.events().iter()
+        .filter(|event| matches!(event, Event::CardTriggered { character: 0, .. }))
+        .count();
+    assert_eq!(triggered, 4);
+}
+
+#[test]
+fn destroyed_features_are_silent() {
+    let mut encounter = with_features(hit(), vec![
+        watcher(Trigger::CardPlayed, -2),
+    ]);
+    encounter.destroy_feature(0);
+    encounter.play_card(0, 1, 0).unwrap();
+    assert_eq!(health(&encounter), vec![20, 17]);
+}
+
+#[test]
+fn trigger_loops_are_cut_off() {
+    let mut echo = watcher(Trigger::TraitBelow { id: MARK, threshold: 1 }, 0);
+    echo.effects = vec![
+        Effect::SetTrait { who: Who::Target, id: MARK, value: 1 },
+        Effect::SetTrait { who: Who::Target, id: MARK, value: 0 },
+    ];
+    let mut encounter = with_features(hit(), vec![echo]);
+    encounter.trigger_limit = 10;
+    encounter.set_trait(0, MARK, 1);
+    encounter.set_trait(0, MARK, 0);
+    encounter.end_turn().unwrap();
+
+    let fired = encounter.events().iter()
+        .filter(|event| matches!(event, Event::FeatureTriggered { .. }))
+        .count();
+    assert_eq!(fired, 10);
+    assert!(encounter.events().contains(&Event::TriggerLimitReached));
+    assert_eq!(encounter.active, 1);
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+// Unbounded trait the phase log below is written to.
+const LOG: TraitID = 9;
+
+// Appends each phase change as a digit to the active character's log.
+fn log_phase(encounter: &mut Encounter, _: FeatureIdx, phase: Phase) {
+    let digit = match phase {
+        Phase::Setup => 0,
+        Phase::Start => 1,
+        Phase::Main => 2,
+        Phase::End => 3,
+    };
+    let cid = encounter.active;
+    let log = encounter.get_trait(cid, LOG);
+    encounter.set_trait(cid, LOG, log * 10 + digit);
+}
+
+fn hands(encounter: &Encounter) -> Vec::<usize> {
+    encounter.characters.iter().map(|ch| ch.deck.hand.len()).collect()
+}
+
+#[test]
+fn nothing_happens_before_start() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    assert_eq!(encounter.phase, Phase::Setup);
+    assert_eq!(encounter.round, 0);
+    assert_eq!(encounter.end_turn(), Err(ActionError::WrongPhase(Phase::Setup)));
+    assert_eq!(
+        encounter.play_card(0, 1, 0),
+        Err(ActionError::WrongPhase(Phase::Setup))
+    );
+    assert!(encounter.events().is_empty());
+
+    assert!(encounter.start());
+    assert!(!encounter.start());
+    assert_eq!(encounter.round, 1);
+
+    let mut empty = Encounter::new(Vec::new(), Vec::new(), &rules, 1);
+    assert!(!empty.start());
+}
+
+#[test]
+fn turns_rotate_and_rounds_advance() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut encounter = encounter(&rules, 3, &[0; 10]);
+    encounter.start();
+    assert_eq!((encounter.active, encounter.phase), (0, Phase::Main));
+    assert_eq!(hands(&encounter), vec![5, 0, 0]);
+
+    encounter.end_turn().unwrap();
+    assert_eq!((encounter.active, encounter.round), (1, 1));
+    assert_eq!(hands(&encounter), vec![0, 5, 0]);
+    assert_eq!(encounter.characters[0].deck.discard.len(), 5);
+
+    encounter.end_turn().unwrap();
+    encounter.end_turn().unwrap();
+    assert_eq!((encounter.active, encounter.round), (0, 2));
+    assert_eq!(encounter.phase, Phase::Main);
+
+    // The deck runs out after two turns and is reshuffled.
+    assert_eq!(hands(&encounter), vec![5, 0, 0]);
+    assert_eq!(encounter.characters[0].deck.deck.len(), 0);
+    encounter.end_turn().unwrap();
+    encounter.end_turn().unwrap();
+    encounter.end_turn().unwrap();
+    assert_eq!(hands(&encounter), vec![5, 0, 0]);
+    assert!(encounter.events().contains(&Event::Reshuffled { character: 0 }));
+}
+
+#[test]
+fn only_the_active_character_plays() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    encounter.start();
+    encounter.draw_card(1);
+    assert_eq!(
+        encounter.play_card(1, 0, 0),
+        Err(ActionError::NotYourTurn { character: 1, active: 0 })
+    );
+    assert_eq!(encounter.play_card(0, 1, 0), Ok(()));
+    assert_eq!(encounter.get_trait(1, HEALTH), 19);
+}
+
+#[test]
+fn turn_events_in_order() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    encounter.draw_count = 1;
+    encounter.start();
+    encounter.end_turn().unwrap();
+
+    let turns: Vec::<&Event> = encounter.events().iter()
+        .filter(|event| !matches!(event, Event::TraitChanged { .. }))
+        .collect();
+    assert_eq!(turns, vec![
+        &Event::TurnStarted { character: 0, round: 1 },
+        &Event::CardDrawn { character: 0, card: turns_card(&encounter, 0) },
+        &Event::Discarded { character: 0, card: turns_card(&encounter, 0) },
+        &Event::TurnEnded { character: 0 },
+        &Event::TurnStarted { character: 1, round: 1 },
+        &Event::CardDrawn { character: 1, card: turns_card(&encounter, 1) },
+    ]);
+}
+
+// The one card character `cid` has drawn so far.
+fn turns_card(encounter: &Encounter, cid: CharacterIdx) -> CardID {
+    let deck = &encounter.characters[cid].deck;
+    *deck.discard.first().or(deck.hand.first()).unwrap()
+}
+
+#[test]
+fn features_see_every_phase() {
+    let mut rules = rules(vec![damage("hit", Targets::Enemy, -1)]);
+    let mut feature = Feature::new("clock", "", idle);
+    feature.on_phase = Some(log_phase);
+    rules.feature_list = Arc::new(vec![feature]);
+    let characters = (0..2).map(|side| character(side, &[0; 10])).collect();
+    let mut encounter = Encounter::new(characters, vec![0], &rules, 1);
+
+    encounter.start();
+    assert_eq!(encounter.get_trait(0, LOG), 12);
+    encounter.end_turn().unwrap();
+    assert_eq!(encounter.get_trait(0, LOG), 123);
+    assert_eq!(encounter.get_trait(1, LOG), 12);
+}
+
+#[test]
+fn nothing_happens_after_the_end() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -20)]);
+    let mut encounter = encounter(&rules, 2, &[0; 10]);
+    encounter.start();
+    encounter.play_card(0, 1, 0).unwrap();
+    assert!(encounter.done);
+    let events = encounter.events().len();
+    assert_eq!(encounter.end_turn(), Err(ActionError::EncounterOver));
+    assert_eq!(encounter.play_card(0, 1, 0), Err(ActionError::EncounterOver));
+    assert_eq!(encounter.events().len(), events);
+}
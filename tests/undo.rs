@@ -0,0 +1,118 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use kier::*;
+
+fn play(game: &mut Game) {
+    let command = Command::PlayCard { character: 0, target: 1, index: 0 };
+    game.apply(command).unwrap();
+}
+
+fn undoable(mode: UndoMode) -> (Rules, Game) {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -2)]);
+    let mut game = game(&rules, 2, &[0; 10], 4);
+    game.set_undo_mode(mode);
+    (rules, game)
+}
+
+#[test]
+fn disabled_by_default() {
+    let rules = rules(vec![damage("hit", Targets::Enemy, -2)]);
+    let mut game = game(&rules, 2, &[0; 10], 4);
+    play(&mut game);
+    assert!(!game.history.can_undo());
+    assert!(!game.undo());
+    assert_eq!(game.encounter.get_trait(1, HEALTH), 18);
+}
+
+#[test]
+fn undo_and_redo() {
+    let (rules, mut game) = undoable(UndoMode::Enabled);
+    let start = replay::checksum(&game.encounter);
+    play(&mut game);
+    let played = replay::checksum(&game.encounter);
+    play(&mut game);
+
+    assert!(game.undo());
+    assert_eq!(replay::checksum(&game.encounter), played);
+    assert!(game.undo());
+    assert_eq!(replay::checksum(&game.encounter), start);
+    assert_eq!(game.encounter.get_trait(1, HEALTH), 20);
+    assert_eq!(game.encounter.characters[0].deck.hand.len(), 5);
+    assert!(game.recording.steps.is_empty());
+    assert!(!game.undo());
+
+    assert!(game.redo());
+    assert_eq!(replay::checksum(&game.encounter), played);
+    assert_eq!(game.recording.steps.len(), 1);
+    assert!(game.recording.verify(&rules).is_ok());
+
+    // A new action drops what was left to redo.
+    game.apply(Command::EndTurn).unwrap();
+    assert!(!game.history.can_redo());
+    assert!(!game.redo());
+    assert!(game.recording.verify(&rules).is_ok());
+}
+
+#[test]
+fn rejected_commands_are_not_undone() {
+    let (_, mut game) = undoable(UndoMode::Enabled);
+    play(&mut game);
+    let bad = Command::PlayCard { character: 0, target: 0, index: 0 };
+    assert!(game.apply(bad).is_err());
+    assert!(game.undo());
+    assert!(!game.history.can_undo());
+    assert_eq!(game.encounter.get_trait(1, HEALTH), 20);
+}
+
+#[test]
+fn history_is_limited() {
+    let (_, mut game) = undoable(UndoMode::Enabled);
+    game.history.limit = 2;
+    for _ in 0..3 {
+        play(&mut game);
+    }
+    assert!(game.undo());
+    assert!(game.undo());
+    assert!(!game.undo());
+    assert_eq!(game.encounter.get_trait(1, HEALTH), 18);
+
+    // Changing the mode keeps the limit but forgets the history.
+    game.set_undo_mode(UndoMode::UntilRandom);
+    assert_eq!(game.history.limit, 2);
+    assert!(!game.history.can_redo());
+}
+
+#[test]
+fn until_random_stops_at_draws() {
+    let (_, mut game) = undoable(UndoMode::UntilRandom);
+    play(&mut game);
+    assert!(game.history.can_undo());
+
+    // Ending the turn deals the next hand, which cannot be taken back.
+    game.apply(Command::EndTurn).unwrap();
+    assert!(!game.history.can_undo());
+    assert!(!game.undo());
+    assert_eq!(game.encounter.active, 1);
+
+    let command = Command::PlayCard { character: 1, target: 0, index: 0 };
+    game.apply(command).unwrap();
+    assert!(game.undo());
+    assert!(!game.undo());
+    assert_eq!(game.encounter.get_trait(0, HEALTH), 20);
+}
+
+#[test]
+fn until_random_stops_at_random_effects() {
+    let mut gamble = damage("gamble", Targets::Enemy, -1);
+    gamble.effects.push(Effect::Script(Arc::new(
+        Script::compile("modify(target, 0, -random(3));").unwrap()
+    )));
+    let rules = rules(vec![gamble]);
+    let mut game = game(&rules, 2, &[0; 10], 4);
+    game.set_undo_mode(UndoMode::UntilRandom);
+    play(&mut game);
+    assert!(!game.history.can_undo());
+}
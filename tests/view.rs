@@ -0,0 +1,82 @@
+mod common;
+
+use common::*;
+use kier::trigger::Trigger;
+use kier::*;
+
+// A reaction that also fires from its holder's hand at every turn start.
+fn rules_with_jab() -> Rules {
+    let mut jab = damage("jab", Targets::Enemy, -1);
+    jab.reaction = true;
+    jab.triggers.push(Trigger::TurnStarted);
+    rules(vec![jab])
+}
+
+fn played(rules: &Rules) -> Game {
+    let mut game = game(rules, 2, &[0; 10], 11);
+    for command in [
+        Command::EndTurn,
+        Command::PlayCard { character: 1, target: 0, index: 0 },
+        Command::Pass { character: 0 },
+        Command::PlayCard { character: 1, target: 0, index: 0 },
+        Command::Respond { character: 0, target: 1, index: 0 },
+    ] {
+        game.apply(command).unwrap();
+    }
+    game
+}
+
+fn about(event: &Event) -> Option<CharacterIdx> {
+    match *event {
+        Event::CardDrawn { character, .. }
+        | Event::CardTriggered { character, .. }
+        | Event::Passed { character } => Some(character),
+        _ => None,
+    }
+}
+
+#[test]
+fn hides_other_hands() {
+    let rules = rules_with_jab();
+    let game = played(&rules);
+    let all = game.encounter.events();
+    assert!(all.iter().any(|e| matches!(e, Event::CardTriggered { character: 0, .. })));
+    assert!(all.iter().any(|e| matches!(e, Event::Passed { character: 0 })));
+    assert!(all.iter().any(|e| matches!(
+        e, Event::ResponseWindow { waiting } if waiting == &[0]
+    )));
+
+    let view = game.view(2);
+    assert_eq!(view.characters[0].hand, None);
+    assert_eq!(view.characters[0].hand_size, 4);
+    assert_eq!(view.characters[1].hand.as_ref().map(Vec::len), Some(3));
+    assert_eq!(view.window.as_ref().unwrap().waiting, vec![1]);
+    assert_eq!(game.view(1).window.unwrap().waiting, Vec::<CharacterIdx>::new());
+
+    let events = game.view_events(2, 0);
+    assert!(events.iter().all(|e| about(e) != Some(0)));
+    assert!(events.iter().any(|e| about(e) == Some(1)));
+    for event in events.iter() {
+        if let Event::ResponseWindow { waiting } = event {
+            assert!(waiting.iter().all(|&cid| cid == 1), "{:?}", waiting);
+        }
+    }
+
+    // Spectators see neither hand.
+    let events = game.view_events(99, 0);
+    assert!(events.iter().all(|e| about(e).is_none()));
+    assert!(game.view(99).characters.iter().all(|c| c.hand.is_none()));
+}
+
+#[test]
+fn own_events_are_kept() {
+    let rules = rules_with_jab();
+    let game = played(&rules);
+    let events = game.view_events(1, 0);
+    let passes = events.iter()
+        .filter(|e| matches!(e, Event::Passed { character: 0 }))
+        .count();
+    assert_eq!(passes, 1);
+    assert!(events.iter().any(|e| matches!(e, Event::CardDrawn { character: 0, .. })));
+    assert_eq!(game.view_events(1, game.encounter.events().len() + 1), Vec::<Event>::new());
+}